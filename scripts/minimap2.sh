@@ -24,7 +24,7 @@ make \
 
 # Collect binaries and create tarball
 FN_TAR="${PROJ}.${OS_TYPE}.tar.gz"
-cbp collect --mode bin -o "${FN_TAR}" minimap2 sdust ||
+cbp collect --package "${BASH_DIR}/../packages/${PROJ}.json" -o "${FN_TAR}" ||
     { echo "==> Error: Failed to create archive"; exit 1; }
 mv "${FN_TAR}" ${BASH_DIR}/../binaries/ ||
     { echo "==> Error: Failed to move archive"; exit 1; }
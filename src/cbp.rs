@@ -16,9 +16,14 @@ fn main() -> anyhow::Result<()> {
         .subcommand(cmd_cbp::local::make_subcommand())
         .subcommand(cmd_cbp::list::make_subcommand())
         .subcommand(cmd_cbp::remove::make_subcommand())
+        .subcommand(cmd_cbp::autoremove::make_subcommand())
+        .subcommand(cmd_cbp::licenses::make_subcommand())
+        .subcommand(cmd_cbp::run::make_subcommand())
         .subcommand(cmd_cbp::info::make_subcommand())
         .subcommand(cmd_cbp::avail::make_subcommand())
         .subcommand(cmd_cbp::check::make_subcommand())
+        .subcommand(cmd_cbp::doctor::make_subcommand())
+        .subcommand(cmd_cbp::self_update::make_subcommand())
         .subcommand(cmd_cbp::tar::make_subcommand())
         .subcommand(cmd_cbp::prefix::make_subcommand())
         .subcommand(cmd_cbp::build::make_subcommand())
@@ -56,18 +61,23 @@ Quick Start:
 
     // Check which subcomamnd the user ran...
     match app.get_matches().subcommand() {
+        Some(("autoremove", sub_matches)) => cmd_cbp::autoremove::execute(sub_matches),
         Some(("avail", sub_matches)) => cmd_cbp::avail::execute(sub_matches),
         Some(("build", sub_matches)) => cmd_cbp::build::execute(sub_matches),
         Some(("check", sub_matches)) => cmd_cbp::check::execute(sub_matches),
         Some(("collect", sub_matches)) => cmd_cbp::collect::execute(sub_matches),
+        Some(("doctor", sub_matches)) => cmd_cbp::doctor::execute(sub_matches),
         Some(("dot", sub_matches)) => cmd_cbp::dot::execute(sub_matches),
         Some(("info", sub_matches)) => cmd_cbp::info::execute(sub_matches),
         Some(("init", sub_matches)) => cmd_cbp::init::execute(sub_matches),
         Some(("install", sub_matches)) => cmd_cbp::install::execute(sub_matches),
+        Some(("licenses", sub_matches)) => cmd_cbp::licenses::execute(sub_matches),
         Some(("list", sub_matches)) => cmd_cbp::list::execute(sub_matches),
         Some(("local", sub_matches)) => cmd_cbp::local::execute(sub_matches),
         Some(("prefix", sub_matches)) => cmd_cbp::prefix::execute(sub_matches),
         Some(("remove", sub_matches)) => cmd_cbp::remove::execute(sub_matches),
+        Some(("run", sub_matches)) => cmd_cbp::run::execute(sub_matches),
+        Some(("self-update", sub_matches)) => cmd_cbp::self_update::execute(sub_matches),
         Some(("snap", sub_matches)) => cmd_cbp::snap::execute(sub_matches),
         Some(("tar", sub_matches)) => cmd_cbp::tar::execute(sub_matches),
         Some(("uninstall", sub_matches)) => cmd_cbp::remove::execute(sub_matches), // Handle alias subcommand
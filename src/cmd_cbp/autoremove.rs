@@ -0,0 +1,48 @@
+use clap::*;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> Command {
+    Command::new("autoremove")
+        .about("Remove dependencies that are no longer needed")
+        .after_help(include_str!("../../docs/help/autoremove.md"))
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .help("Show what would be removed without removing anything")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// Execute autoremove command
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+    let dry_run = args.get_flag("dry-run");
+
+    let orphans = cbp_dirs.orphan_packages(&[])?;
+    if orphans.is_empty() {
+        println!("==> No unused dependencies found");
+        return Ok(());
+    }
+
+    for package in &orphans {
+        if dry_run {
+            println!("==> Would remove {}:", package);
+        } else {
+            println!("==> Removing {}:", package);
+        }
+        cbp_dirs.remove_package(package, &orphans, dry_run)?;
+        println!("    Done");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use clap::*;
+use cmd_lib::*;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> clap::Command {
+    clap::Command::new("bump")
+        .about("Update a package to a new upstream version")
+        .after_help(include_str!("../../../docs/help/build_bump.md"))
+        .arg(
+            Arg::new("package")
+                .help("Package name to update")
+                .required(true)
+                .num_args(1)
+                .value_name("PACKAGE"),
+        )
+        .arg(
+            Arg::new("new_version")
+                .help("New version")
+                .required(true)
+                .num_args(1)
+                .value_name("VERSION"),
+        )
+        .arg(
+            Arg::new("no-test")
+                .long("no-test")
+                .help("Skip installing and testing the rebuilt package")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("base")
+                .long("base")
+                .help("Base directory containing packages/ and binaries/")
+                .num_args(1)
+                .value_name("BASE")
+                .default_value("."),
+        )
+}
+
+/// Execute bump subcommand
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
+    let pkg = args.get_one::<String>("package").unwrap();
+    let new_version = args.get_one::<String>("new_version").unwrap();
+    let opt_no_test = args.get_flag("no-test");
+
+    let proxy_args: Vec<String> = args
+        .get_one::<String>("proxy")
+        .map(|p| vec!["--proxy".to_string(), p.to_string()])
+        .unwrap_or_default();
+    // Borrowed, as `run_cmd!` consumes its `$[...]` arguments
+    let proxy_args = &proxy_args;
+
+    let package = cbp::read_package_json(&base_dir, pkg)?;
+    let old_version = package.version.clone();
+    if &old_version == new_version {
+        return Err(anyhow::anyhow!(
+            "Package {} is already at version {}",
+            pkg,
+            new_version
+        ));
+    }
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    println!("==> Bumping {}: {} -> {}", pkg, old_version, new_version);
+
+    // Restore the original JSON and archives if any step fails
+    let json_path = base_dir.join("packages").join(format!("{}.json", pkg));
+    let original = std::fs::read_to_string(&json_path)?;
+    let mut archives: Vec<std::path::PathBuf> = ["linux", "macos", "windows", "font"]
+        .iter()
+        .map(|t| {
+            base_dir
+                .join("binaries")
+                .join(format!("{}.{}.tar.gz", pkg, t))
+        })
+        .collect();
+    archives.push(base_dir.join("sources").join(format!("{}.tar.gz", pkg)));
+    let backup_dir = tempfile::Builder::new().prefix("cbp-bump-").tempdir()?;
+    let backups = backup_files(&archives, backup_dir.path())?;
+
+    let result = (|| -> anyhow::Result<()> {
+        cbp::update_package_json(&base_dir, pkg, |json| {
+            json["version"] = serde_json::Value::String(new_version.to_string());
+            if let Some(downloads) = json["downloads"].as_object_mut() {
+                for dl_obj in downloads.values_mut() {
+                    replace_version(dl_obj, &old_version, new_version);
+                }
+            }
+            Ok(())
+        })?;
+
+        let package = cbp::read_package_json(&base_dir, pkg)?;
+        for (key, dl_obj) in &package.downloads {
+            println!("    {}: {}", key, dl_obj.url());
+        }
+
+        let cbp = std::env::current_exe()?.display().to_string();
+
+        println!("-> Refreshing checksums");
+        run_cmd!(${cbp} build checksum ${pkg} --base ${base_dir} $[proxy_args])?;
+
+        // Rebuild from the new downloads
+        let os_type = cbp::get_os_type()?;
+        let archive_type = match package.pkg_type {
+            cbp::PackageType::Prebuild => {
+                run_cmd!(${cbp} build prebuild ${pkg} --base ${base_dir} $[proxy_args])?;
+                Some(os_type.as_str())
+            }
+            cbp::PackageType::Font => {
+                run_cmd!(${cbp} build font ${pkg} --base ${base_dir} $[proxy_args])?;
+                Some("font")
+            }
+            _ => {
+                if package.downloads.contains_key("source") {
+                    run_cmd!(${cbp} build source ${pkg} --base ${base_dir} $[proxy_args])?;
+                }
+                println!(
+                    "-> Build binaries of {} package {} before testing",
+                    package.pkg_type, pkg
+                );
+                None
+            }
+        };
+
+        // Install the rebuilt package and run its tests
+        if let Some(t) = archive_type {
+            let archive = base_dir
+                .join("binaries")
+                .join(format!("{}.{}.tar.gz", pkg, t));
+            if opt_no_test {
+                println!("-> Skipping tests");
+            } else if !archive.exists() {
+                println!("-> No {} package to test", t);
+            } else {
+                println!("-> Testing {}", archive.display());
+                // Installed into a scratch home, leaving the user's one alone
+                let test_dir = tempfile::Builder::new().prefix("cbp-bump-").tempdir()?;
+                let test_home = test_dir.path();
+                run_cmd!(
+                    cd ${base_dir};
+                    ${cbp} local -t ${t} ${pkg} --dir ${test_home}
+                )?;
+                run_cmd!(${cbp} build test ${pkg} --base ${base_dir} --dir ${test_home})?;
+            }
+        }
+
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        std::fs::write(&json_path, original)?;
+        restore_files(&archives, &backups)?;
+        return Err(anyhow::anyhow!(
+            "Failed to bump {}, {} and its archives restored: {}",
+            pkg,
+            json_path.display(),
+            e
+        ));
+    }
+
+    println!("==> Bumped {} to {}", pkg, new_version);
+    Ok(())
+}
+
+/// Copy the existing files into `backup_dir`
+///
+/// Returns the backup of each file, `None` for files that did not exist.
+fn backup_files(
+    files: &[std::path::PathBuf],
+    backup_dir: &std::path::Path,
+) -> anyhow::Result<Vec<Option<std::path::PathBuf>>> {
+    let mut backups = vec![];
+    for (i, file) in files.iter().enumerate() {
+        if file.is_file() {
+            let backup = backup_dir.join(i.to_string());
+            std::fs::copy(file, &backup)?;
+            backups.push(Some(backup));
+        } else {
+            backups.push(None);
+        }
+    }
+    Ok(backups)
+}
+
+/// Put back the files saved by [`backup_files`], removing ones created since
+fn restore_files(
+    files: &[std::path::PathBuf],
+    backups: &[Option<std::path::PathBuf>],
+) -> anyhow::Result<()> {
+    for (file, backup) in files.iter().zip(backups) {
+        match backup {
+            Some(backup) => {
+                std::fs::copy(backup, file)?;
+            }
+            None if file.exists() => std::fs::remove_file(file)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Replace literal versions in URLs, download names, file rules and resources
+///
+/// Only whole versions are replaced, see [`cbp::replace_version_token`]. Fields
+/// using `{{version}}` follow the new version without changes.
+fn replace_version(dl_obj: &mut serde_json::Value, old: &str, new: &str) {
+    for field in ["url", "download_name", "binary", "tar", "clean"] {
+        match dl_obj.get_mut(field) {
+            Some(serde_json::Value::String(s)) => {
+                *s = cbp::replace_version_token(s, old, new)
+            }
+            Some(serde_json::Value::Array(items)) => {
+                for item in items {
+                    if let serde_json::Value::String(s) = item {
+                        *s = cbp::replace_version_token(s, old, new);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Both the paths and their targets may carry the version
+    for field in ["rename", "symlink"] {
+        if let Some(serde_json::Value::Object(rules)) = dl_obj.get_mut(field) {
+            *rules = std::mem::take(rules)
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        serde_json::Value::String(s) => serde_json::Value::String(
+                            cbp::replace_version_token(&s, old, new),
+                        ),
+                        value => value,
+                    };
+                    (cbp::replace_version_token(&key, old, new), value)
+                })
+                .collect();
+        }
+    }
+
+    if let Some(serde_json::Value::Array(resources)) = dl_obj.get_mut("resources") {
+        for resource in resources {
+            replace_version(resource, old, new);
+        }
+    }
+}
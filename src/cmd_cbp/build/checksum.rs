@@ -0,0 +1,159 @@
+use clap::*;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> clap::Command {
+    clap::Command::new("checksum")
+        .about("Fill in sha256 checksums of package downloads")
+        .after_help(include_str!("../../../docs/help/build_checksum.md"))
+        .arg(
+            Arg::new("packages")
+                .help("Package names to update")
+                .required(true)
+                .num_args(1..)
+                .value_name("PACKAGES"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .short('t')
+                .help("Only update this download")
+                .num_args(1)
+                .value_name("TYPE")
+                .value_parser(["source", "linux", "macos", "windows", "font"]),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("base")
+                .long("base")
+                .help("Base directory containing packages/")
+                .num_args(1)
+                .value_name("BASE")
+                .default_value("."),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// Execute checksum subcommand
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
+    let opt_type = args.get_one::<String>("type");
+    let agent = cbp::create_http_agent(args.get_one::<String>("proxy"))?;
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    for pkg in args.get_many::<String>("packages").unwrap() {
+        println!("==> Computing checksums for package: {}", pkg);
+
+        // Validates the package before rewriting it
+        let package = cbp::read_package_json(&base_dir, pkg)?;
+
+        let mut checksums = Vec::new();
+        let mut resource_checksums = Vec::new();
+        for (key, dl_obj) in &package.downloads {
+            if opt_type.is_some_and(|t| t != key) {
+                continue;
+            }
+
+            // Git sources are pinned by `rev`
+            if dl_obj.git.is_some() {
+                println!("    {}: git source, skipped", key);
+            } else {
+                let sha256 = fetch_sha256(&agent, key, dl_obj, &cbp_dirs.home)?;
+                checksums.push((key.to_string(), sha256));
+            }
+
+            for (i, resource) in dl_obj.resources.iter().enumerate() {
+                let label = format!("{}.resources[{}]", key, i);
+                let sha256 =
+                    fetch_sha256(&agent, &label, &resource.download, &cbp_dirs.home)?;
+                resource_checksums.push((key.to_string(), i, sha256));
+            }
+        }
+
+        cbp::update_package_json(&base_dir, pkg, |json| {
+            for (key, sha256) in &checksums {
+                let dl_obj =
+                    json["downloads"][key].as_object_mut().ok_or_else(|| {
+                        anyhow::anyhow!("Download configuration '{}' not found", key)
+                    })?;
+                set_after_url(dl_obj, sha256);
+            }
+            for (key, i, sha256) in &resource_checksums {
+                let res_obj = json["downloads"][key]["resources"][i]
+                    .as_object_mut()
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Resource {} of '{}' not found", i, key)
+                    })?;
+                set_after_url(res_obj, sha256);
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Download a file and report its checksum against the recorded one
+fn fetch_sha256(
+    agent: &ureq::Agent,
+    label: &str,
+    dl_obj: &cbp::Download,
+    home: &std::path::Path,
+) -> anyhow::Result<String> {
+    println!("-> Downloading {} from {}", label, dl_obj.url());
+    let temp_dir = tempfile::tempdir()?;
+    let temp_file = temp_dir.path().join("download.tmp");
+    cbp::download_file(&dl_obj.urls, &temp_file, agent, None, home)?;
+    let sha256 = cbp::sha256_file(&temp_file)?;
+
+    match &dl_obj.sha256 {
+        Some(old) if old.eq_ignore_ascii_case(&sha256) => {
+            println!("    {}: unchanged", label);
+        }
+        Some(old) => {
+            println!("    {}: {} -> {}", label, old, sha256);
+        }
+        None => println!("    {}: {}", label, sha256),
+    }
+    Ok(sha256)
+}
+
+/// Set `sha256` in a download object, placing new keys right after `url`
+fn set_after_url(dl_obj: &mut serde_json::Map<String, serde_json::Value>, sha256: &str) {
+    let value = serde_json::Value::String(sha256.to_string());
+    if let Some(existing) = dl_obj.get_mut("sha256") {
+        *existing = value;
+        return;
+    }
+
+    let entries: Vec<_> = std::mem::take(dl_obj).into_iter().collect();
+    for (k, v) in entries {
+        let is_url = k == "url";
+        dl_obj.insert(k, v);
+        if is_url {
+            dl_obj.insert("sha256".to_string(), value.clone());
+        }
+    }
+    if !dl_obj.contains_key("sha256") {
+        dl_obj.insert("sha256".to_string(), value);
+    }
+}
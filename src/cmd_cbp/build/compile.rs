@@ -0,0 +1,556 @@
+use clap::*;
+use cmd_lib::*;
+use std::path::{Path, PathBuf};
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> clap::Command {
+    clap::Command::new("compile")
+        .about("Compile packages from source")
+        .after_help(include_str!("../../../docs/help/build_compile.md"))
+        .arg(
+            Arg::new("packages")
+                .help("Package names to compile")
+                .required(true)
+                .num_args(1..)
+                .value_name("PACKAGES"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .short('t')
+                .help("Target OS (default: current OS)")
+                .num_args(1)
+                .value_name("TYPE")
+                .value_parser(["linux", "macos", "windows"]),
+        )
+        .arg(
+            Arg::new("native")
+                .long("native")
+                .help("Use the host toolchain instead of zig")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("Number of parallel jobs")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .default_value("8"),
+        )
+        .arg(
+            Arg::new("base")
+                .long("base")
+                .help("Base directory containing packages/, sources/ and binaries/")
+                .num_args(1)
+                .value_name("BASE")
+                .default_value("."),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// Toolchain settings shared by all build systems
+struct Toolchain {
+    os_type: String,
+    /// zig target triple, `None` for native builds
+    target: Option<String>,
+    jobs: usize,
+    include: PathBuf,
+    lib: PathBuf,
+}
+
+impl Toolchain {
+    /// C compiler command line
+    fn cc(&self) -> String {
+        match &self.target {
+            Some(target) => format!("zig cc -target {}", target),
+            None => "cc".to_string(),
+        }
+    }
+
+    /// C++ compiler command line
+    fn cxx(&self) -> String {
+        match &self.target {
+            Some(target) => format!("zig c++ -target {}", target),
+            None => "c++".to_string(),
+        }
+    }
+
+    fn cflags(&self) -> String {
+        format!("-I{}", self.include.display())
+    }
+
+    fn ldflags(&self) -> String {
+        format!("-L{}", self.lib.display())
+    }
+
+    /// Environment variables for configure scripts
+    fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![
+            ("CC".to_string(), self.cc()),
+            ("CXX".to_string(), self.cxx()),
+            ("CFLAGS".to_string(), self.cflags()),
+            ("CPPFLAGS".to_string(), self.cflags()),
+            ("CXXFLAGS".to_string(), self.cflags()),
+            ("LDFLAGS".to_string(), self.ldflags()),
+        ];
+        if self.target.is_some() {
+            env.push(("AR".to_string(), "zig ar".to_string()));
+            env.push(("RANLIB".to_string(), "zig ranlib".to_string()));
+        }
+        env
+    }
+
+    /// Name of an executable on the target OS
+    fn exe_name(&self, name: &str) -> String {
+        if self.os_type == "windows" && !name.ends_with(".exe") {
+            format!("{}.exe", name)
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// Execute compile subcommand
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+
+    let os_type = match args.get_one::<String>("type") {
+        Some(t) => t.to_string(),
+        None => cbp::get_os_type()?,
+    };
+    let opt_native = args.get_flag("native");
+    if opt_native && os_type != cbp::get_os_type()? {
+        return Err(anyhow::anyhow!(
+            "--native can only build for the current OS"
+        ));
+    }
+
+    let toolchain = Toolchain {
+        target: (!opt_native).then(|| zig_target(&os_type).to_string()),
+        os_type,
+        jobs: *args.get_one::<usize>("jobs").unwrap(),
+        include: cbp_dirs.home.join("include"),
+        lib: cbp_dirs.home.join("lib"),
+    };
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    for pkg in args.get_many::<String>("packages").unwrap() {
+        println!("==> Compiling package: {}", pkg);
+
+        let package = cbp::read_package_json(&base_dir, pkg)?;
+        let build = &package.build;
+
+        let source_archive = base_dir.join("sources").join(format!("{}.tar.gz", pkg));
+        if !source_archive.exists() {
+            return Err(anyhow::anyhow!(
+                "Source file {} not found, run `cbp build source {}` first",
+                source_archive.display(),
+                pkg
+            ));
+        }
+
+        let temp_dir = tempfile::tempdir()?;
+        let src_dir = extract_source(&source_archive, temp_dir.path(), pkg)?;
+        let prefix = temp_dir.path().join("collect");
+        let target_path = cbp::target_binary_path(&base_dir, pkg, &toolchain.os_type)?;
+
+        // Git sources record the resolved commit in `.cbp-git`
+        let mut metadata = package.metadata("source");
+        if let Ok(content) = std::fs::read_to_string(src_dir.join(".cbp-git")) {
+            metadata.commit = content
+                .lines()
+                .find_map(|line| line.strip_prefix("commit "))
+                .map(str::to_string)
+                .or(metadata.commit);
+        }
+        let metadata = serde_json::to_string(&metadata)?;
+
+        let outputs = match package.pkg_type {
+            cbp::PackageType::Rust => build_rust(&src_dir, build, &toolchain)?,
+            cbp::PackageType::Make => build_make(&src_dir, pkg, build, &toolchain)?,
+            cbp::PackageType::Cmake => build_cmake(&src_dir, &prefix, build, &toolchain)?,
+            cbp::PackageType::Autotools => {
+                build_autotools(&src_dir, &prefix, build, &toolchain)?
+            }
+            pkg_type => {
+                return Err(anyhow::anyhow!(
+                    "Package type '{}' cannot be compiled, expected rust, make, cmake or autotools",
+                    pkg_type
+                ))
+            }
+        };
+
+        let mut strip_args = vec![];
+        if build.split_debug {
+            strip_args.push("--split-debug");
+        } else if build.strip {
+            strip_args.push("--strip");
+        }
+
+        // A `collect` section declares the package layout itself
+        let outputs = match package.collect {
+            Some(_) => Outputs::Layout,
+            None => outputs,
+        };
+
+        // Collect the listed binaries, or everything installed into the prefix
+        let cbp = std::env::current_exe()?.display().to_string();
+        match outputs {
+            Outputs::Layout => {
+                let json_path = base_dir.join("packages").join(format!("{}.json", pkg));
+                println!("-> Collecting the layout in {}", json_path.display());
+                run_cmd!(
+                    cd ${src_dir};
+                    ${cbp} collect --package ${json_path} -o ${target_path} --metadata ${metadata} --licenses ${src_dir} $[strip_args]
+                )?;
+            }
+            Outputs::Binaries(dir, paths) => {
+                let paths: Vec<String> =
+                    paths.iter().map(|p| toolchain.exe_name(p)).collect();
+                for path in &paths {
+                    if !dir.join(path).is_file() {
+                        return Err(anyhow::anyhow!(
+                            "Binary {} not found in {}",
+                            path,
+                            dir.display()
+                        ));
+                    }
+                }
+                println!("-> Collecting {}", paths.join(", "));
+                run_cmd!(
+                    cd ${dir};
+                    ${cbp} collect --mode bin -o ${target_path} --metadata ${metadata} --licenses ${src_dir} $[strip_args] $[paths]
+                )?;
+            }
+            Outputs::Prefix => {
+                let license_files = cbp::find_license_files(&src_dir)?;
+                for path in cbp::copy_licenses(&license_files, &prefix, pkg)? {
+                    println!("-> License {}", path);
+                }
+                for path in
+                    cbp::relocate_configs(&prefix, &[prefix.display().to_string()])?
+                {
+                    println!("-> Relocated {}", path);
+                }
+
+                // Debug info sits beside the prefix, outside of the package
+                let debug_dir = temp_dir.path().join("debug");
+                if build.strip || build.split_debug {
+                    let stripped = cbp::strip_binaries(
+                        &prefix,
+                        build.split_debug.then_some(debug_dir.as_path()),
+                    )?;
+                    for (path, before, after) in &stripped {
+                        println!(
+                            "-> Stripped {} ({} -> {})",
+                            path,
+                            cbp::format_size(*before),
+                            cbp::format_size(*after)
+                        );
+                    }
+                }
+
+                println!("-> Creating tarball from {}", prefix.display());
+                run_cmd!(
+                    ${cbp} tar ${prefix} -o ${target_path} --metadata ${metadata}
+                )?;
+
+                if debug_dir.is_dir() {
+                    let debug_path = cbp::debug_archive_name(&target_path);
+                    println!("-> Creating debug tarball {}", debug_path);
+                    run_cmd!(
+                        ${cbp} tar ${debug_dir} -o ${debug_path} --metadata ${metadata}
+                    )?;
+                }
+            }
+        }
+        println!("-> Package created: {}", target_path);
+    }
+
+    Ok(())
+}
+
+/// Build results to be packed
+enum Outputs {
+    /// Binaries relative to the given directory
+    Binaries(PathBuf, Vec<String>),
+    /// Files installed into the collect prefix
+    Prefix,
+    /// Files selected by the `collect` section, relative to the source directory
+    Layout,
+}
+
+/// zig target triple for C/C++ builds, same as `scripts/common.sh`
+fn zig_target(os_type: &str) -> &'static str {
+    match os_type {
+        "linux" => "x86_64-linux-gnu.2.17",
+        "macos" => "aarch64-macos-none",
+        _ => "x86_64-windows-gnu",
+    }
+}
+
+/// Rust target triple for `cargo zigbuild`, same as `scripts/rust.sh`
+fn rust_target(os_type: &str) -> &'static str {
+    match os_type {
+        "linux" => "x86_64-unknown-linux-gnu.2.17",
+        "macos" => "aarch64-apple-darwin",
+        _ => "x86_64-pc-windows-gnu",
+    }
+}
+
+/// Extract the source archive and locate the source directory
+///
+/// Looks for `{pkg}` or `{pkg}-*`, then falls back to a single top-level directory.
+fn extract_source(archive: &Path, dest: &Path, pkg: &str) -> anyhow::Result<PathBuf> {
+    println!("-> Extracting {}", archive.display());
+    cbp::open_archive(archive)?.unpack(dest)?;
+
+    let dirs: Vec<PathBuf> = std::fs::read_dir(dest)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+
+    let by_name = dirs.iter().find(|p| {
+        let name = p.file_name().unwrap().to_string_lossy();
+        name == pkg || name.starts_with(&format!("{}-", pkg))
+    });
+
+    match (by_name, dirs.len()) {
+        (Some(dir), _) => Ok(dir.clone()),
+        (None, 1) => Ok(dirs[0].clone()),
+        _ => Ok(dest.to_path_buf()),
+    }
+}
+
+/// Run a build command in the source directory
+fn run(
+    dir: &Path,
+    program: &str,
+    args: &[String],
+    env: &[(String, String)],
+) -> anyhow::Result<()> {
+    println!("-> Running: {} {}", program, args.join(" "));
+    let status = std::process::Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .current_dir(dir)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to execute {}: {}", program, e))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} failed with {}", program, status));
+    }
+    Ok(())
+}
+
+/// Build a cargo project with `cargo zigbuild`
+fn build_rust(
+    src_dir: &Path,
+    build: &cbp::Build,
+    toolchain: &Toolchain,
+) -> anyhow::Result<Outputs> {
+    let env: Vec<(String, String)> = build.env.clone().into_iter().collect();
+
+    let mut cargo_args = vec![];
+    match &toolchain.target {
+        Some(_) => {
+            cargo_args.push("zigbuild".to_string());
+            cargo_args.push("--target".to_string());
+            cargo_args.push(rust_target(&toolchain.os_type).to_string());
+        }
+        None => cargo_args.push("build".to_string()),
+    }
+    cargo_args.push("--release".to_string());
+
+    if !build.features.is_empty() {
+        cargo_args.push("--features".to_string());
+        cargo_args.push(build.features.join(","));
+    }
+    if build.no_default_features {
+        cargo_args.push("--no-default-features".to_string());
+    }
+    run(src_dir, "cargo", &cargo_args, &env)?;
+
+    // Honor CARGO_TARGET_DIR like scripts/rust.sh does
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| src_dir.join("target"));
+    let release_dir = match &toolchain.target {
+        Some(_) => target_dir
+            .join(rust_target(&toolchain.os_type).trim_end_matches(".2.17"))
+            .join("release"),
+        None => target_dir.join("release"),
+    };
+
+    let mut bins = build.binaries.clone();
+    if bins.is_empty() {
+        bins = cargo_bin_targets(src_dir)?;
+    }
+
+    Ok(Outputs::Binaries(release_dir, bins))
+}
+
+/// Names of binary targets from `cargo metadata`
+fn cargo_bin_targets(src_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let output = std::process::Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .current_dir(src_dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("cargo metadata failed"));
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+
+    let bins: Vec<String> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|p| p["targets"].as_array().into_iter().flatten())
+        .filter(|t| t["kind"][0] == "bin")
+        .filter_map(|t| t["name"].as_str().map(String::from))
+        .collect();
+
+    Ok(bins)
+}
+
+/// Build a plain Makefile project
+fn build_make(
+    src_dir: &Path,
+    pkg: &str,
+    build: &cbp::Build,
+    toolchain: &Toolchain,
+) -> anyhow::Result<Outputs> {
+    let env: Vec<(String, String)> = build.env.clone().into_iter().collect();
+
+    // Pass the toolchain as make variables, which override Makefile assignments
+    let mut make_args = vec![
+        "-j".to_string(),
+        toolchain.jobs.to_string(),
+        format!("CC={}", toolchain.cc()),
+        format!("CXX={}", toolchain.cxx()),
+    ];
+    if !env.iter().any(|(k, _)| k == "CFLAGS") {
+        make_args.push(format!("CFLAGS={} -O3", toolchain.cflags()));
+    }
+    if !env.iter().any(|(k, _)| k == "LDFLAGS") {
+        make_args.push(format!("LDFLAGS={}", toolchain.ldflags()));
+    }
+    make_args.extend(build.make_args.iter().cloned());
+    make_args.extend(build.targets.iter().cloned());
+    run(src_dir, "make", &make_args, &env)?;
+
+    // Default to a binary named after the package
+    let mut bins = build.binaries.clone();
+    if bins.is_empty() {
+        bins.push(pkg.to_string());
+    }
+
+    Ok(Outputs::Binaries(src_dir.to_path_buf(), bins))
+}
+
+/// Build a CMake project
+fn build_cmake(
+    src_dir: &Path,
+    prefix: &Path,
+    build: &cbp::Build,
+    toolchain: &Toolchain,
+) -> anyhow::Result<Outputs> {
+    let mut env = toolchain.env();
+    if toolchain.target.is_some() {
+        // zig takes the target from CMAKE_*_COMPILER_TARGET instead
+        env.retain(|(k, _)| k != "CC" && k != "CXX");
+        env.push(("ASM".to_string(), "zig cc".to_string()));
+        env.push(("CC".to_string(), "zig cc".to_string()));
+        env.push(("CXX".to_string(), "zig c++".to_string()));
+    }
+    env.extend(build.env.clone());
+
+    let mut cmake_args = vec![
+        "-S".to_string(),
+        ".".to_string(),
+        "-B".to_string(),
+        "build".to_string(),
+        "-DCMAKE_BUILD_TYPE=Release".to_string(),
+        format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display()),
+    ];
+    if let Some(target) = &toolchain.target {
+        for lang in ["ASM", "C", "CXX"] {
+            cmake_args.push(format!("-DCMAKE_{}_COMPILER_TARGET={}", lang, target));
+        }
+    }
+    cmake_args.extend(build.configure.iter().cloned());
+    run(src_dir, "cmake", &cmake_args, &env)?;
+
+    let mut build_args = vec![
+        "--build".to_string(),
+        "build".to_string(),
+        "--parallel".to_string(),
+        toolchain.jobs.to_string(),
+    ];
+    for target in &build.targets {
+        build_args.push("--target".to_string());
+        build_args.push(target.clone());
+    }
+    run(src_dir, "cmake", &build_args, &env)?;
+
+    // Listed binaries are taken from the build tree, otherwise install everything
+    let bins = build.binaries.clone();
+    if !bins.is_empty() {
+        return Ok(Outputs::Binaries(src_dir.to_path_buf(), bins));
+    }
+    run(
+        src_dir,
+        "cmake",
+        &["--install".to_string(), "build".to_string()],
+        &env,
+    )?;
+
+    Ok(Outputs::Prefix)
+}
+
+/// Build a configure/make project
+fn build_autotools(
+    src_dir: &Path,
+    prefix: &Path,
+    build: &cbp::Build,
+    toolchain: &Toolchain,
+) -> anyhow::Result<Outputs> {
+    let mut env = toolchain.env();
+    env.extend(build.env.clone());
+
+    let mut configure_args = vec![format!("--prefix={}", prefix.display())];
+    configure_args.extend(build.configure.iter().cloned());
+    run(src_dir, "./configure", &configure_args, &env)?;
+
+    let mut make_args = vec!["-j".to_string(), toolchain.jobs.to_string()];
+    make_args.extend(build.make_args.iter().cloned());
+    make_args.extend(build.targets.iter().cloned());
+    run(src_dir, "make", &make_args, &env)?;
+
+    // Listed binaries are taken from the build tree, otherwise install everything
+    let bins = build.binaries.clone();
+    if !bins.is_empty() {
+        return Ok(Outputs::Binaries(src_dir.to_path_buf(), bins));
+    }
+    let mut install_args = build.make_args.clone();
+    install_args.push("install".to_string());
+    run(src_dir, "make", &install_args, &env)?;
+
+    Ok(Outputs::Prefix)
+}
@@ -28,6 +28,15 @@ pub fn make_subcommand() -> clap::Command {
                 .value_name("BASE")
                 .default_value("."),
         )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
 }
 
 /// Execute font subcommand
@@ -38,6 +47,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
     let opt_proxy_url = args.get_one::<String>("proxy");
     let agent = cbp::create_http_agent(opt_proxy_url)?;
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
 
     let cbp = std::env::current_exe()?.display().to_string();
 
@@ -48,47 +58,36 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         println!("==> Processing font package: {}", pkg);
 
         // Read and validate package configuration
-        let json = cbp::read_package_json(&base_dir, pkg)?;
+        let package = cbp::read_package_json(&base_dir, pkg)?;
 
         // Ensure it's a font package
-        if json["type"].as_str() != Some("font") {
+        if package.pkg_type != cbp::PackageType::Font {
             return Err(anyhow::anyhow!("Package {} is not a font package", pkg));
         }
 
         // Get download configuration
-        let dl_obj = json["downloads"]["font"]
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Download configuration not found"))?;
+        let dl_obj = package.download("font")?;
 
         let temp_dir = tempfile::tempdir()?;
         let temp_file = cbp::temp_download_path(&temp_dir, dl_obj);
 
         // Download font file
-        let url = dl_obj["url"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Font URL not found"))?;
-
-        // Allow overriding the URL via environment variable for testing
-        let url = if let Some(pos) = url.find("://") {
-            let rest = &url[pos + 3..];
-            format!("{}/{}", cbp::github_release_url(), rest)
-        } else {
-            url.to_string()
-        };
-
-        println!("-> Downloading from {}", url);
-        cbp::download_file(&url, &temp_file, &agent)?;
+        println!("-> Downloading from {}", dl_obj.url());
+        cbp::download_file(
+            &dl_obj.urls,
+            &temp_file,
+            &agent,
+            dl_obj.sha256.as_deref(),
+            &cbp_dirs.home,
+        )?;
 
         // Check if extraction is needed
-        let needs_extract = cbp::needs_extract(&url, dl_obj);
+        let needs_extract = cbp::needs_extract(&temp_file, dl_obj)?;
 
         if needs_extract {
             cbp::extract_archive(&temp_dir, &temp_file, dl_obj)?;
-        } else if dl_obj.get("binary").is_some() {
+        } else if let Some(binary_name) = dl_obj.binary.first() {
             // For single-file downloads, move to target binary name
-            let binary_name = dl_obj["binary"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Binary name not found"))?;
             let target_path = temp_dir.path().join(binary_name);
             cbp::move_file_or_dir(&temp_file, &target_path)?;
         }
@@ -104,12 +103,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         let binary_paths = cbp::find_binary_files(temp_dir.path(), dl_obj)?;
 
         let target_path = cbp::target_font_path(&base_dir, pkg)?;
+        let metadata = serde_json::to_string(&package.metadata("font"))?;
         let temp_path = temp_dir.path().canonicalize()?;
 
         // Change to temp directory and collect files
         run_cmd!(
             cd ${temp_path};
-            ${cbp} collect --mode font -o ${target_path} $[binary_paths]
+            ${cbp} collect --mode font -o ${target_path} --metadata ${metadata} $[binary_paths]
         )?;
 
         println!("-> Font package created successfully");
@@ -0,0 +1,136 @@
+use clap::*;
+use std::io::Read;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> clap::Command {
+    clap::Command::new("lint")
+        .about("Check binaries in package archives for portability problems")
+        .after_help(include_str!("../../../docs/help/build_lint.md"))
+        .arg(
+            Arg::new("archives")
+                .help("Package archives to check")
+                .required(true)
+                .num_args(1..)
+                .value_name("ARCHIVES"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .short('t')
+                .help("Package type, defaults to the one in the archive name")
+                .num_args(1)
+                .value_name("TYPE")
+                .value_parser(["linux", "macos", "windows"]),
+        )
+        .arg(
+            Arg::new("glibc")
+                .long("glibc")
+                .help("Highest glibc version linux binaries may require")
+                .num_args(1)
+                .value_name("VERSION")
+                .default_value("2.17"),
+        )
+}
+
+/// Execute lint subcommand
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let opt_type = args.get_one::<String>("type");
+    let glibc = args.get_one::<String>("glibc").unwrap();
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    let mut failed = vec![];
+    for archive in args.get_many::<String>("archives").unwrap() {
+        let path = std::path::Path::new(archive);
+        let os_type = match opt_type {
+            Some(t) => t.to_string(),
+            None => archive_type(path).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Cannot tell the package type of {}, use --type",
+                    archive
+                )
+            })?,
+        };
+
+        println!("==> Linting {} ({})", archive, os_type);
+        let violations = lint_archive(path, &os_type, glibc)?;
+        if violations > 0 {
+            failed.push(format!("{} ({} violations)", archive, violations));
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!("Lint failed: {}", failed.join(", ")));
+    }
+    println!("==> No violations found");
+
+    Ok(())
+}
+
+/// Package type from a `{pkg}.{type}.tar.gz` name
+fn archive_type(path: &std::path::Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    name.split('.')
+        .find(|part| ["linux", "macos", "windows"].contains(part))
+        .map(str::to_string)
+}
+
+/// Check every binary in the archive, returning the number of violations
+fn lint_archive(
+    path: &std::path::Path,
+    os_type: &str,
+    glibc: &str,
+) -> anyhow::Result<usize> {
+    // Read binaries up front, libraries they need may come later in the archive
+    let mut package_files = vec![];
+    let mut binaries = vec![];
+    let mut archive = cbp::open_archive(path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let name = entry_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&entry_path)
+            .to_string();
+        package_files.push(name);
+
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let mut bytes = vec![];
+        entry.read_to_end(&mut bytes)?;
+        if let Some(info) = cbp::inspect_binary(&bytes) {
+            let stray = cbp::stray_paths(&bytes);
+            binaries.push((entry_path, info, stray));
+        }
+    }
+
+    let mut count = 0;
+    for (entry_path, info, stray) in &binaries {
+        println!(
+            "-> {}: {} {}{}",
+            entry_path,
+            info.format,
+            info.archs.join("+"),
+            info.glibc
+                .as_ref()
+                .map(|v| format!(", GLIBC_{}", v))
+                .unwrap_or_default()
+        );
+
+        let mut violations = cbp::lint_binary(info, os_type, glibc, &package_files);
+        violations.extend(stray.iter().map(|p| format!("embeds build path {}", p)));
+        for violation in &violations {
+            println!("    error: {}", violation);
+        }
+        count += violations.len();
+    }
+
+    println!("    {} binaries, {} violations", binaries.len(), count);
+    Ok(count)
+}
@@ -0,0 +1,276 @@
+use clap::*;
+use serde::Serialize;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> clap::Command {
+    clap::Command::new("livecheck")
+        .about("Check packages for newer upstream versions")
+        .after_help(include_str!("../../../docs/help/build_livecheck.md"))
+        .arg(
+            Arg::new("packages")
+                .help("Package names to check, defaults to all packages")
+                .num_args(0..)
+                .value_name("PACKAGES"),
+        )
+        .arg(
+            Arg::new("outdated")
+                .long("outdated")
+                .help("Only report outdated packages")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("base")
+                .long("base")
+                .help("Base directory containing packages/")
+                .num_args(1)
+                .value_name("BASE")
+                .default_value("."),
+        )
+}
+
+/// Result of checking one package
+#[derive(Debug, Serialize)]
+struct Check {
+    name: String,
+    current: String,
+    latest: Option<String>,
+    source: Option<String>,
+    outdated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Execute livecheck subcommand
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
+    let opt_outdated = args.get_flag("outdated");
+    let is_json = args.get_flag("json");
+    let agent = cbp::create_http_agent(args.get_one::<String>("proxy"))?;
+
+    let packages: Vec<String> = match args.get_many::<String>("packages") {
+        Some(pkgs) => pkgs.cloned().collect(),
+        None => all_packages(&base_dir)?,
+    };
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    let mut checks = Vec::new();
+    for pkg in &packages {
+        let check = check_package(&agent, &base_dir, pkg);
+        if !is_json && (!opt_outdated || check.outdated) {
+            print_check(&check);
+        }
+        checks.push(check);
+    }
+
+    if opt_outdated {
+        checks.retain(|c| c.outdated);
+    }
+
+    if is_json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        let outdated = checks.iter().filter(|c| c.outdated).count();
+        println!("==> {} of {} packages outdated", outdated, packages.len());
+    }
+
+    Ok(())
+}
+
+fn print_check(check: &Check) {
+    match (&check.latest, &check.error) {
+        (_, Some(error)) => {
+            println!("    {}: {} (error: {})", check.name, check.current, error)
+        }
+        (Some(latest), None) if check.outdated => {
+            println!("    {}: {} -> {}", check.name, check.current, latest)
+        }
+        (Some(_), None) => {
+            println!("    {}: {} (up to date)", check.name, check.current)
+        }
+        (None, None) => println!("    {}: {} (unknown)", check.name, check.current),
+    }
+}
+
+/// Names of all package files in `packages/`
+fn all_packages(base_dir: &std::path::Path) -> anyhow::Result<Vec<String>> {
+    let mut packages = Vec::new();
+    for entry in std::fs::read_dir(base_dir.join("packages"))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(stem) = path.file_stem() {
+                packages.push(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    packages.sort();
+    Ok(packages)
+}
+
+fn check_package(agent: &ureq::Agent, base_dir: &std::path::Path, pkg: &str) -> Check {
+    let mut check = Check {
+        name: pkg.to_string(),
+        current: String::new(),
+        latest: None,
+        source: None,
+        outdated: false,
+        error: None,
+    };
+
+    let package = match cbp::read_package_json(base_dir, pkg) {
+        Ok(package) => package,
+        Err(e) => {
+            check.error = Some(e.to_string());
+            return check;
+        }
+    };
+    check.current = package.version.clone();
+
+    match latest_version(agent, &package) {
+        Ok(Some((source, latest))) => {
+            check.outdated = cbp::compare_versions(&latest, &package.version)
+                == std::cmp::Ordering::Greater;
+            check.source = Some(source);
+            check.latest = Some(latest);
+        }
+        Ok(None) => {}
+        Err(e) => check.error = Some(e.to_string()),
+    }
+
+    check
+}
+
+/// Find the latest upstream version and where it came from
+///
+/// Returns `None` if the package has neither a `livecheck` rule nor a GitHub URL.
+fn latest_version(
+    agent: &ureq::Agent,
+    package: &cbp::Package,
+) -> anyhow::Result<Option<(String, String)>> {
+    let livecheck = package.livecheck.clone().unwrap_or_default();
+    let re = match &livecheck.regex {
+        Some(regex) => regex::Regex::new(regex)
+            .map_err(|e| anyhow::anyhow!("Invalid livecheck.regex: {}", e))?,
+        None => regex::Regex::new(r"(\d+(?:[._]\d+)*)").unwrap(),
+    };
+    if re.captures_len() < 2 {
+        return Err(anyhow::anyhow!(
+            "livecheck.regex needs a capture group for the version"
+        ));
+    }
+
+    // A page to scan
+    if let Some(url) = &livecheck.url {
+        if livecheck.regex.is_none() {
+            return Err(anyhow::anyhow!("livecheck.url requires livecheck.regex"));
+        }
+        let body = get(agent, url)?
+            .ok_or_else(|| anyhow::anyhow!("{} not found", url))?
+            .into_string()?;
+        let latest = max_version(&re, body.lines())
+            .ok_or_else(|| anyhow::anyhow!("No version found in {}", url))?;
+        return Ok(Some((url.to_string(), latest)));
+    }
+
+    // GitHub releases and tags
+    let Some(repo) = github_repo(package) else {
+        return Ok(None);
+    };
+    let api_url = cbp::github_api_url();
+
+    // The latest release is the upstream's own choice, unless a regex narrows tags
+    if livecheck.regex.is_none() {
+        let url = format!("{}/repos/{}/releases/latest", api_url, repo);
+        if let Some(resp) = get(agent, &url)? {
+            let release: serde_json::Value = resp.into_json()?;
+            if let Some(version) = release["tag_name"]
+                .as_str()
+                .and_then(|tag| re.captures(tag)?.get(1).map(|m| m.as_str().to_string()))
+            {
+                return Ok(Some((format!("https://github.com/{}", repo), version)));
+            }
+        }
+    }
+
+    let url = format!("{}/repos/{}/tags?per_page=100", api_url, repo);
+    let tags: Vec<serde_json::Value> = get(agent, &url)?
+        .ok_or_else(|| anyhow::anyhow!("Repository {} not found", repo))?
+        .into_json()?;
+    let names = tags
+        .iter()
+        .filter_map(|tag| tag["name"].as_str())
+        .filter(|name| livecheck.regex.is_some() || !is_prerelease(name));
+    let latest = max_version(&re, names)
+        .ok_or_else(|| anyhow::anyhow!("No version found in tags of {}", repo))?;
+
+    Ok(Some((format!("https://github.com/{}", repo), latest)))
+}
+
+/// GET a URL, returning `None` on 404
+fn get(agent: &ureq::Agent, url: &str) -> anyhow::Result<Option<ureq::Response>> {
+    let mut req = agent.get(url).set("user-agent", "cbp");
+    if url.starts_with(&cbp::github_api_url()) {
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            req = req.set("authorization", &format!("Bearer {}", token));
+        }
+    }
+
+    match req.call() {
+        Ok(resp) => Ok(Some(resp)),
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Extract `owner/repo` from the homepage or download URLs
+fn github_repo(package: &cbp::Package) -> Option<String> {
+    let re = regex::Regex::new(r"github\.com/([\w.-]+)/([\w.-]+)").unwrap();
+
+    // Prefer the source download over prebuilt binaries
+    let mut urls = vec![package.homepage.as_str()];
+    if let Some(source) = package.downloads.get("source") {
+        urls.push(source.url());
+        urls.extend(source.git.as_deref());
+    }
+    urls.extend(package.downloads.values().map(|d| d.url()));
+
+    urls.iter().find_map(|url| {
+        re.captures(url)
+            .map(|caps| format!("{}/{}", &caps[1], caps[2].trim_end_matches(".git")))
+    })
+}
+
+/// Highest version captured by `re` over all inputs
+fn max_version<'a>(
+    re: &regex::Regex,
+    inputs: impl Iterator<Item = &'a str>,
+) -> Option<String> {
+    inputs
+        .flat_map(|input| re.captures_iter(input))
+        // Optional groups may not take part in a match
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+        .max_by(|a, b| cbp::compare_versions(a, b))
+}
+
+/// Tags such as `v2.0.0-rc1` or `1.0-beta`
+fn is_prerelease(tag: &str) -> bool {
+    let re = regex::Regex::new(r"(?i)alpha|beta|[-._]?rc\d*$|[-._]pre").unwrap();
+    re.is_match(tag)
+}
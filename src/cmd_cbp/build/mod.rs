@@ -1,6 +1,11 @@
 use clap::*;
 
+pub mod bump;
+pub mod checksum;
+pub mod compile;
 pub mod font;
+pub mod lint;
+pub mod livecheck;
 pub mod prebuild;
 pub mod source;
 pub mod test;
@@ -13,7 +18,12 @@ pub fn make_subcommand() -> Command {
         .about("Build package commands")
         .after_help(include_str!("../../../docs/help/build.md"))
         .subcommand_required(true)
+        .subcommand(bump::make_subcommand())
+        .subcommand(checksum::make_subcommand())
+        .subcommand(compile::make_subcommand())
         .subcommand(font::make_subcommand())
+        .subcommand(lint::make_subcommand())
+        .subcommand(livecheck::make_subcommand())
         .subcommand(prebuild::make_subcommand())
         .subcommand(source::make_subcommand())
         .subcommand(test::make_subcommand())
@@ -24,7 +34,12 @@ pub fn make_subcommand() -> Command {
 /// Execute pkg command
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     match args.subcommand() {
+        Some(("bump", sub_args)) => bump::execute(sub_args),
+        Some(("checksum", sub_args)) => checksum::execute(sub_args),
+        Some(("compile", sub_args)) => compile::execute(sub_args),
         Some(("font", sub_args)) => font::execute(sub_args),
+        Some(("lint", sub_args)) => lint::execute(sub_args),
+        Some(("livecheck", sub_args)) => livecheck::execute(sub_args),
         Some(("prebuild", sub_matches)) => prebuild::execute(sub_matches),
         Some(("source", sub_args)) => source::execute(sub_args),
         Some(("test", sub_args)) => test::execute(sub_args),
@@ -37,6 +37,15 @@ pub fn make_subcommand() -> clap::Command {
                 .value_name("BASE")
                 .default_value("."),
         )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
 }
 
 /// Execute prebuild subcommand
@@ -47,6 +56,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let base_dir = std::path::PathBuf::from(args.get_one::<String>("base").unwrap());
     let opt_proxy_url = args.get_one::<String>("proxy");
     let agent = cbp::create_http_agent(opt_proxy_url)?;
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
 
     let cbp = std::env::current_exe()?.display().to_string();
 
@@ -60,14 +70,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         println!("==> Processing prebuild package: {}", pkg);
 
         // Read package configuration
-        let json = cbp::read_package_json(&base_dir, pkg)?;
+        let package = cbp::read_package_json(&base_dir, pkg)?;
 
         // Process each available OS type
-        let downloads = json["downloads"]
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Downloads configuration not found"))?;
-
-        for (os_type, download_obj) in downloads {
+        for (os_type, dl_obj) in &package.downloads {
             // Skip if type is specified and doesn't match
             if let Some(type_filter) = opt_type {
                 if type_filter != os_type {
@@ -77,41 +83,29 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 continue;
             }
 
-            let dl_obj = download_obj.as_object().ok_or_else(|| {
-                anyhow::anyhow!("Download configuration not found for {}", os_type)
-            })?;
-
             println!("-> Processing for OS: {}", os_type);
 
             let temp_dir = tempfile::tempdir()?;
 
             // Download file
-            let url = dl_obj["url"]
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("URL not found"))?;
-
-            // Allow overriding the base URL via environment variable for testing
-            let url = if let Some(path_part) = url.strip_prefix("https://github.com") {
-                format!("{}{}", cbp::github_release_url(), path_part)
-            } else {
-                url.to_string()
-            };
-
             let temp_file = cbp::temp_download_path(&temp_dir, dl_obj);
 
-            println!("-> Downloading from {}", url);
-            cbp::download_file(&url, &temp_file, &agent)?;
+            println!("-> Downloading from {}", dl_obj.url());
+            cbp::download_file(
+                &dl_obj.urls,
+                &temp_file,
+                &agent,
+                dl_obj.sha256.as_deref(),
+                &cbp_dirs.home,
+            )?;
 
             // Check if extraction is needed
-            let needs_extract = cbp::needs_extract(&url, dl_obj);
+            let needs_extract = cbp::needs_extract(&temp_file, dl_obj)?;
 
             if needs_extract {
                 cbp::extract_archive(&temp_dir, &temp_file, dl_obj)?;
-            } else if dl_obj.get("binary").is_some() {
+            } else if let Some(binary_name) = dl_obj.binary.first() {
                 // For single binary files, use copy and delete instead of rename to handle cross-device scenarios
-                let binary_name = dl_obj["binary"]
-                    .as_str()
-                    .ok_or_else(|| anyhow::anyhow!("Binary name not found"))?;
                 let target_path = temp_dir.path().join(binary_name);
                 cbp::move_file_or_dir(&temp_file, &target_path)?;
             }
@@ -130,18 +124,14 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             }
 
             let target_path = cbp::target_binary_path(&base_dir, pkg, os_type)?;
+            let metadata = serde_json::to_string(&package.metadata(os_type))?;
             let temp_path = temp_dir.path().canonicalize()?;
 
             // Add shebang option if enabled
-            let shebang_opt =
-                if dl_obj.get("shebang").and_then(|v| v.as_bool()) == Some(true) {
-                    "--shebang"
-                } else {
-                    ""
-                };
+            let shebang_opt = if dl_obj.shebang { "--shebang" } else { "" };
 
             // Only process binary files if binary configuration exists
-            if dl_obj.get("binary").is_some() {
+            if !dl_obj.binary.is_empty() {
                 // Find binary files
                 let binary_paths = cbp::find_binary_files(temp_dir.path(), dl_obj)?;
 
@@ -159,24 +149,35 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 // Change to temp directory and collect files
                 run_cmd!(
                     cd ${temp_path};
-                    ${cbp} collect --mode bin ${shebang_opt} -o ${target_path} $[binary_paths]
+                    ${cbp} collect --mode bin ${shebang_opt} -o ${target_path} --metadata ${metadata} --licenses ${temp_path} $[binary_paths]
                 )?;
-            } else if dl_obj.get("tar").is_some() {
-                let tar = dl_obj["tar"]
-                    .as_str()
-                    .ok_or_else(|| anyhow::anyhow!("URL not found"))?;
+            } else if let Some(tar) = &dl_obj.tar {
+                let tar_path = temp_path.join(tar);
+                let mut license_files = cbp::find_license_files(&tar_path)?;
+                if license_files.is_empty() {
+                    license_files = cbp::find_license_files(&temp_path)?;
+                }
+                for path in cbp::copy_licenses(&license_files, &tar_path, pkg)? {
+                    println!("-> License {}", path);
+                }
+
                 println!("-> Creating tarball from {}", tar);
                 run_cmd!(
                     cd ${temp_path};
-                    ${cbp} tar ${tar} -o ${target_path}
+                    ${cbp} tar ${tar} -o ${target_path} --metadata ${metadata}
                 )?;
             } else {
+                let license_files = cbp::find_license_files(&temp_path)?;
+                for path in cbp::copy_licenses(&license_files, &temp_path, pkg)? {
+                    println!("-> License {}", path);
+                }
+
                 println!("-> Creating tarball from .");
                 // Change to temp directory and collect files
                 // cbp collect can't handle symlinks
                 run_cmd!(
                     cd ${temp_path};
-                    ${cbp} tar . -o ${target_path}
+                    ${cbp} tar . -o ${target_path} --metadata ${metadata}
                 )?;
             }
             println!("-> Package created successfully");
@@ -1,5 +1,6 @@
 use clap::*;
 use cmd_lib::*;
+use tracing::warn;
 
 /// Create clap subcommand arguments
 pub fn make_subcommand() -> clap::Command {
@@ -28,6 +29,15 @@ pub fn make_subcommand() -> clap::Command {
                 .value_name("BASE")
                 .default_value("."),
         )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
 }
 
 /// Execute download subcommand
@@ -41,6 +51,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     // Set up HTTP agent with optional proxy
     let opt_proxy_url = args.get_one::<String>("proxy");
     let agent = cbp::create_http_agent(opt_proxy_url)?;
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
 
     let cbp = std::env::current_exe()?.display().to_string();
 
@@ -52,60 +63,72 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         println!("==> Processing source package: {}", pkg);
 
         // Read and validate package configuration
-        let json = cbp::read_package_json(&base_dir, pkg)?;
+        let package = cbp::read_package_json(&base_dir, pkg)?;
 
         // Get source download configuration
-        let dl_obj = json["downloads"]["source"]
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Download configuration not found"))?;
+        let dl_obj = package.download("source")?;
 
         let temp_dir = tempfile::tempdir()?;
 
-        // Download file
-        let url = dl_obj["url"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Source URL not found"))?;
-
-        // Allow overriding the base URL via environment variable for testing
-        let url = if let Some(path_part) = url.strip_prefix("https://github.com") {
-            format!("{}{}", cbp::github_release_url(), path_part)
-        } else {
-            url.to_string()
-        };
-
         let temp_file = cbp::temp_download_path(&temp_dir, dl_obj);
-
-        // Process file after download
-        println!("-> Downloading from {}", url);
-        cbp::download_file(&url, &temp_file, &agent)?;
-
         let target_path = cbp::target_source_path(&base_dir, pkg)?;
 
-        if dl_obj.len() == 1 {
-            cbp::move_file_or_dir(&temp_file, std::path::Path::new(&target_path))?;
-            println!("-> Successfully downloaded and processed");
-            continue;
-        }
-
-        // Check if extraction is needed
-        let needs_extract = cbp::needs_extract(&url, dl_obj);
-
-        if needs_extract {
-            println!("-> Processing source archive");
-            cbp::extract_archive(&temp_dir, &temp_file, dl_obj)?;
+        if let (Some(git), Some(rev)) = (&dl_obj.git, &dl_obj.rev) {
+            println!("-> Cloning {} at {}", git, rev);
+            let commit = cbp::clone_git_source(&temp_dir, pkg, git, rev)?;
+            println!("-> Resolved commit {}", commit);
         } else {
-            cbp::normalize_line_endings(&temp_file)?;
+            // Download file
+            println!("-> Downloading from {}", dl_obj.url());
+            cbp::download_file(
+                &dl_obj.urls,
+                &temp_file,
+                &agent,
+                dl_obj.sha256.as_deref(),
+                &cbp_dirs.home,
+            )?;
+
+            if dl_obj.is_url_only() {
+                cbp::move_file_or_dir(&temp_file, std::path::Path::new(&target_path))?;
+                println!("-> Successfully downloaded and processed");
+                continue;
+            }
+
+            // Check if extraction is needed
+            let needs_extract = cbp::needs_extract(&temp_file, dl_obj)?;
+
+            if needs_extract {
+                println!("-> Processing source archive");
+                cbp::extract_archive(&temp_dir, &temp_file, dl_obj)?;
+            } else {
+                cbp::normalize_line_endings(&temp_file)?;
+            }
         }
 
         let temp_path = temp_dir.path().canonicalize()?;
 
         cbp::handle_rename(&temp_dir, dl_obj)?;
+        // Patched first, as resources may add top-level entries beside the source
+        cbp::apply_patches(&temp_dir, &temp_file, &base_dir, pkg, dl_obj)?;
+        for resource in &dl_obj.resources {
+            fetch_resource(&agent, resource, &temp_dir, &cbp_dirs.home)?;
+        }
         cbp::clean_files(&temp_dir, dl_obj)?;
 
         if temp_file.exists() {
             std::fs::remove_file(&temp_file)?;
         }
 
+        // Builds ship these into share/licenses/{pkg}/
+        let license_files = cbp::find_license_files(&temp_path)?;
+        if license_files.is_empty() {
+            warn!("No license file found in the source of {}", pkg);
+        }
+        for path in &license_files {
+            let rel = path.strip_prefix(&temp_path).unwrap_or(path);
+            println!("-> License file {}", rel.display());
+        }
+
         run_cmd!(
             cd ${temp_path};
             ${cbp} tar . -o ${target_path}
@@ -115,3 +138,67 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Download a resource and move its files into `dest` of the source tree
+fn fetch_resource(
+    agent: &ureq::Agent,
+    resource: &cbp::Resource,
+    temp_dir: &tempfile::TempDir,
+    home: &std::path::Path,
+) -> anyhow::Result<()> {
+    let dest = std::path::Path::new(&resource.dest);
+    if dest.is_absolute()
+        || dest
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+    {
+        return Err(anyhow::anyhow!(
+            "Resource dest must be a relative path inside the source: {}",
+            resource.dest
+        ));
+    }
+
+    let dl_obj = &resource.download;
+    let res_dir = tempfile::tempdir()?;
+    let url = dl_obj.url();
+
+    println!("-> Downloading resource {} from {}", resource.dest, url);
+    let res_file = cbp::temp_download_path(&res_dir, dl_obj);
+    cbp::download_file(
+        &dl_obj.urls,
+        &res_file,
+        agent,
+        dl_obj.sha256.as_deref(),
+        home,
+    )?;
+
+    if cbp::needs_extract(&res_file, dl_obj)? {
+        cbp::extract_archive(&res_dir, &res_file, dl_obj)?;
+        if res_file.exists() {
+            std::fs::remove_file(&res_file)?;
+        }
+    } else if dl_obj.download_name.is_none() {
+        // Keep the file name from the URL
+        let name = url
+            .split(['?', '#'])
+            .next()
+            .and_then(|u| u.rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("download");
+        std::fs::rename(&res_file, res_dir.path().join(name))?;
+    }
+
+    cbp::handle_rename(&res_dir, dl_obj)?;
+    cbp::clean_files(&res_dir, dl_obj)?;
+
+    let dest = temp_dir.path().join(dest);
+    std::fs::create_dir_all(&dest)?;
+    for entry in std::fs::read_dir(res_dir.path())? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name() {
+            cbp::move_file_or_dir(&path, &dest.join(name))?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,6 +1,5 @@
 use anyhow::Context;
 use clap::*;
-use serde_json::Value;
 use std::process::Command;
 use tracing::warn;
 
@@ -56,11 +55,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     let mut has_error = false;
     for pkg in packages {
-        let json = cbp::read_package_json(&base_dir, &pkg)?;
+        let package = cbp::read_package_json(&base_dir, &pkg)?;
 
         // Check if tests are defined
-        let tests = json.get("tests").and_then(Value::as_array);
-        if tests.is_none() {
+        if package.tests.is_empty() {
             warn!("No test cases defined for package {}", pkg);
             continue;
         }
@@ -68,24 +66,10 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         println!("==> Testing package: {}\n", pkg);
 
         // Execute test cases
-        for (i, test) in tests.unwrap().iter().enumerate() {
-            let name = test
-                .get("name")
-                .and_then(Value::as_str)
-                .map(String::from)
-                .unwrap_or(format!("test #{}", i));
-
-            let cmd = test
-                .get("command")
-                .and_then(Value::as_str)
-                .context("Missing 'command' field in test case")?;
-
-            let ignore_exit_code = test
-                .get("ignore_exit_code")
-                .and_then(Value::as_bool)
-                .unwrap_or(false);
-
-            let pattern = test.get("pattern").and_then(Value::as_str);
+        for test in &package.tests {
+            let name = &test.name;
+            let cmd = test.command.as_str();
+            let ignore_exit_code = test.ignore_exit_code;
 
             print!("==> Running test '{}'... ", name);
 
@@ -97,62 +81,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             {
                 cmd.to_string()
             } else {
-                let cmd_path = cbp_dirs.bin.join(cmd);
-                if os_type == "windows" {
-                    if cmd_path.exists() {
-                        cmd_path.to_string_lossy().to_string()
-                    } else {
-                        // Try common Windows executable extensions
-                        let extensions = [".exe", ".ps1", ".bat", ".cmd"];
-                        extensions
-                            .iter()
-                            .map(|ext| {
-                                cmd_path.with_extension(ext.trim_start_matches("."))
-                            })
-                            .find(|path| path.exists())
-                            .map_or_else(
-                                || cmd_path.to_string_lossy().to_string(),
-                                |path| path.to_string_lossy().to_string(),
-                            )
-                    }
-                } else {
-                    cmd_path.to_string_lossy().to_string()
-                }
+                cbp::resolve_bin_path(&cbp_dirs.bin, cmd)
+                    .to_string_lossy()
+                    .to_string()
             };
 
-            // Get arguments
-            let args = test
-                .get("args")
-                .map(|a| match a {
-                    Value::String(s) => {
-                        // Handle command substitution in string
-                        if s.contains("$(cbp prefix)") {
-                            vec![s.replace(
-                                "$(cbp prefix)",
-                                &cbp_dirs.home.to_string_lossy(),
-                            )]
-                        } else {
-                            vec![s.clone()]
-                        }
-                    }
-                    Value::Array(arr) => arr
-                        .iter()
-                        .filter_map(Value::as_str)
-                        .map(|s| {
-                            // Handle command substitution in array elements
-                            if s.contains("$(cbp prefix)") {
-                                s.replace(
-                                    "$(cbp prefix)",
-                                    &cbp_dirs.home.to_string_lossy(),
-                                )
-                            } else {
-                                s.to_string()
-                            }
-                        })
-                        .collect(),
-                    _ => Vec::new(),
-                })
-                .unwrap_or_default();
+            // Get arguments, handling command substitution
+            let args = test.resolved_args(&cbp_dirs.home);
 
             // Execute command
             let output = if os_type == "windows" {
@@ -217,19 +152,15 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 continue;
             }
 
-            if let Some(pattern) = pattern {
-                // Use (?m) to enable multiline mode, making $ match end of each line
-                let pattern = format!("(?m){}", pattern);
-                if regex::Regex::new(&pattern)?.is_match(&combined_output) {
-                    println!("PASSED");
-                } else {
-                    println!("FAILED");
-                    println!("  - Output does not match pattern: {}", pattern);
-                    println!("  - Output: {}", combined_output);
-                    has_error = true;
-                }
-            } else {
+            // Use (?m) to enable multiline mode, making $ match end of each line
+            let pattern = format!("(?m){}", test.pattern);
+            if regex::Regex::new(&pattern)?.is_match(&combined_output) {
                 println!("PASSED");
+            } else {
+                println!("FAILED");
+                println!("  - Output does not match pattern: {}", pattern);
+                println!("  - Output: {}", combined_output);
+                has_error = true;
             }
         }
     }
@@ -92,7 +92,12 @@ fn validate_package(
     pkg: &str,
     schema: &jsonschema::JSONSchema,
 ) -> anyhow::Result<()> {
-    let json = cbp::read_package_json(base_dir, pkg)?;
+    // Check field types against the package model first for precise errors
+    cbp::read_package_json(base_dir, pkg)?;
+
+    let json_path = base_dir.join("packages").join(format!("{}.json", pkg));
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(json_path)?)?;
 
     schema.validate(&json).map_err(|errors| {
         let error_messages: Vec<String> = errors.map(|e| format!("{}", e)).collect();
@@ -21,27 +21,11 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     println!("==> Unmanaged files in {}:", cbp_dirs.home.display());
 
-    // Collect all known files from installed packages
-    let mut known_files = Vec::new();
-    if cbp_dirs.records.exists() {
-        let files = cbp::find_files(&cbp_dirs.records, Some("*.files"))?;
-        for file in files {
-            let content = std::fs::read_to_string(cbp_dirs.records.join(&file))?;
-            known_files.extend(content.lines().map(|s| s.to_string()));
-        }
-    }
-
-    // Find and display files not in the known list
+    // Find and display files not in any package record
     let mut count = 0u64;
-    let all_files = cbp::find_files(&cbp_dirs.home, None)?;
-    for file in all_files {
-        if !cbp::is_cbp_file(&file)
-            && !cbp::is_system_file(&file)
-            && !known_files.contains(&file)
-        {
-            println!("  {}", file);
-            count += 1;
-        }
+    for file in cbp_dirs.unmanaged_files()? {
+        println!("  {}", file);
+        count += 1;
     }
 
     if count == 0 {
@@ -1,6 +1,5 @@
 use clap::{Arg, ArgAction, Command};
 use cmd_lib::*;
-use std::io::{Read, Seek, SeekFrom};
 use tracing::{debug, warn};
 
 pub fn make_subcommand() -> Command {
@@ -10,7 +9,7 @@ pub fn make_subcommand() -> Command {
         .arg(
             Arg::new("sources")
                 .help("Source files, directories or a list")
-                .required(true)
+                .required_unless_present("package")
                 .num_args(1..)
                 .index(1),
         )
@@ -21,6 +20,12 @@ pub fn make_subcommand() -> Command {
                 .action(ArgAction::Append)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("symlink")
+                .long("symlink")
+                .help("Create --copy aliases as symlinks instead of copies")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("ignore")
                 .long("ignore")
@@ -28,6 +33,34 @@ pub fn make_subcommand() -> Command {
                 .action(ArgAction::Append)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Only collect files matching the pattern")
+                .action(ArgAction::Append)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("package")
+                .long("package")
+                .help("Collect the layout declared in a package JSON")
+                .value_name("JSON")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .help("Build prefix to relocate in .pc, .la and CMake config files")
+                .action(ArgAction::Append)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("licenses")
+                .long("licenses")
+                .help("Directory to gather LICENSE and COPYING files from")
+                .value_name("DIR")
+                .action(ArgAction::Set),
+        )
         .arg(
             Arg::new("output")
                 .short('o')
@@ -49,18 +82,50 @@ pub fn make_subcommand() -> Command {
                 .help("Fix shebang lines in script files")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("strip")
+                .long("strip")
+                .help("Strip symbols and debug info from ELF and Mach-O binaries")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("split-debug")
+                .long("split-debug")
+                .help("Strip binaries, saving debug info to a .debug archive")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("metadata")
+                .long("metadata")
+                .help("Package metadata JSON to embed in the archive")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("compression")
+                .long("compression")
+                .help("Compression format of the archive")
+                .value_parser(cbp::CompressionFormat::NAMES)
+                .default_value("gzip")
+                .action(ArgAction::Set),
+        )
 }
 
 pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
     //----------------------------
     // Args
     //----------------------------
-    let sources = matches.get_many::<String>("sources").unwrap();
+    let sources: Vec<&String> = matches
+        .get_many::<String>("sources")
+        .map(|s| s.collect())
+        .unwrap_or_default();
+    let package_json = matches.get_one::<String>("package");
     let mode = matches.get_one::<String>("mode").unwrap();
     let is_vcpkg = mode == "vcpkg";
+    let compression = matches.get_one::<String>("compression").unwrap();
+    let extension = compression.parse::<cbp::CompressionFormat>()?.extension();
 
-    // Get first source for output name
-    let first_source = sources.clone().next().unwrap();
+    // Get first source, or the package JSON, for output name
+    let first_source = sources.first().copied().or(package_json).unwrap().as_str();
 
     // output name
     let output: String =
@@ -78,7 +143,7 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
                         .get(2)
                         .map(|s| s.split('-').nth(1).unwrap_or(""))
                         .unwrap_or("");
-                    format!("{}.{}.tar.gz", pkg_name, platform)
+                    format!("{}.{}.{}", pkg_name, platform, extension)
                 } else {
                     let path = std::path::Path::new(first_source);
                     let name = path
@@ -86,7 +151,7 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
                         .or_else(|| path.file_name())
                         .and_then(|n| n.to_str())
                         .unwrap_or("output");
-                    format!("{}.tar.gz", name)
+                    format!("{}.{}", name, extension)
                 }
             });
 
@@ -107,11 +172,35 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
         })
         .unwrap_or_default();
 
-    // Parse ignore patterns
-    let ignore_patterns: Vec<String> = matches
+    // Layout from the `collect` section of the package JSON
+    let (package_name, layout) = match package_json {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path, e))?;
+            let package = cbp::Package::from_json(&content)
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", path, e))?;
+            let layout = package
+                .collect
+                .ok_or_else(|| anyhow::anyhow!("No collect section in {}", path))?;
+            (Some(package.name), Some(layout))
+        }
+        None => (None, None),
+    };
+
+    // Parse ignore and include patterns
+    let ignore_patterns = matches
         .get_many::<String>("ignore")
-        .map(|ignores| ignores.map(|s| s.to_string()).collect())
-        .unwrap_or_default();
+        .into_iter()
+        .flatten()
+        .chain(layout.iter().flat_map(|l| l.ignore.iter()))
+        .map(|p| p.parse())
+        .collect::<anyhow::Result<Vec<cbp::PathPattern>>>()?;
+    let include_patterns = matches
+        .get_many::<String>("include")
+        .into_iter()
+        .flatten()
+        .map(|p| p.parse())
+        .collect::<anyhow::Result<Vec<cbp::PathPattern>>>()?;
 
     let cbp = std::env::current_exe()?.display().to_string();
 
@@ -150,7 +239,9 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
         }
     } else {
         // Read and parse list file
-        let source_path = sources.into_iter().next().unwrap();
+        let source_path = sources
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Mode {} needs a list file", mode))?;
         let source_file = std::path::Path::new(source_path);
         if !source_file.exists() {
             anyhow::bail!("Source file not found: {}", source_path);
@@ -188,41 +279,69 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
 
     debug!("base_dir = {:#?}", base_dir);
 
+    let mut entries = vec![];
+    for line in &file_list {
+        let parts = match get_path_parts(line, is_vcpkg) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let relative_path = get_relative_path(&parts, mode, is_vcpkg);
+        let is_bin = parts[0] == "tools" || parts[0] == "bin";
+        entries.push(Entry {
+            src_path: base_dir.join(line),
+            line: parts.join("/"),
+            relative_path,
+            is_bin,
+        });
+    }
+    if let Some(layout) = &layout {
+        entries.extend(layout_entries(layout)?);
+    }
+
     // Create temporary directory
     let temp_dir = tempfile::Builder::new().prefix("cbp-collect-").tempdir()?;
+    let symlink_aliases = matches.get_flag("symlink");
+    let mut links = vec![];
 
     // Collect files
-    for line in &file_list {
-        if should_skip_file(line, &ignore_patterns) {
+    for Entry {
+        src_path,
+        line,
+        relative_path,
+        is_bin,
+    } in entries
+    {
+        if should_skip_file(
+            &[&line, &relative_path],
+            &ignore_patterns,
+            &include_patterns,
+        ) {
             continue;
         }
 
-        let src_path = base_dir.join(line);
         if !src_path.exists() {
             warn!("File not found: {}", src_path.display());
             continue;
         }
 
-        let parts = match get_path_parts(line, is_vcpkg) {
-            Some(parts) => parts,
-            None => continue,
-        };
-
-        let relative_path = get_relative_path(&parts, mode, is_vcpkg);
         let dest_path = temp_dir.path().join(&relative_path);
 
         if let Some(parent) = dest_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
+        // Keep symlinks that stay inside the package
+        if let Some(target) = package_link(&src_path, &relative_path)? {
+            create_symlink(&target, &dest_path)?;
+            links.push((src_path, dest_path));
+            continue;
+        }
+
         if src_path.is_file() {
-            // can't handle symlinks
             std::fs::copy(&src_path, &dest_path)?;
 
             // Check if it's a Windows executable in tools or bin directory
-            if (parts[0] == "tools" || parts[0] == "bin")
-                && is_windows_executable(&src_path)?
-            {
+            if is_bin && cbp::is_windows_executable(&src_path)? {
                 let dest_exe = dest_path.with_extension("exe");
                 if dest_path != dest_exe {
                     // Use move_file_or_dir to handle cross-device scenarios
@@ -231,7 +350,7 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
             }
 
             #[cfg(unix)]
-            if parts[0] == "tools" || parts[0] == "bin" {
+            if is_bin {
                 use std::os::unix::fs::PermissionsExt;
                 std::fs::set_permissions(
                     &dest_path,
@@ -244,22 +363,215 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
                 cbp::fix_shebang(&dest_path)?;
             }
 
-            process_file_aliases(&dest_path, &copy_map)?;
+            process_file_aliases(&dest_path, &copy_map, symlink_aliases)?;
+        }
+    }
+
+    // Links whose targets were not collected become copies
+    for (src_path, dest_path) in &links {
+        if !dest_path.exists() {
+            debug!("Dangling symlink, copying {}", src_path.display());
+            std::fs::remove_file(dest_path)?;
+            std::fs::copy(src_path, dest_path)?;
+        }
+    }
+
+    // License texts go to share/licenses/{pkg}/
+    let license_files = match matches.get_one::<String>("licenses") {
+        Some(dir) => cbp::find_license_files(std::path::Path::new(dir))?,
+        // vcpkg ports install theirs as share/{port}/copyright
+        None if is_vcpkg => cbp::find_files(&temp_dir.path().join("share"), None)
+            .unwrap_or_default()
+            .iter()
+            .filter(|f| {
+                f.split_once('/').is_some_and(|(_, name)| {
+                    !name.contains('/') && cbp::is_license_file(name)
+                })
+            })
+            .map(|f| temp_dir.path().join("share").join(f))
+            .collect(),
+        // `--package` runs in the source directory
+        None if layout.is_some() => cbp::find_license_files(&std::env::current_dir()?)?,
+        None => vec![],
+    };
+    let pkg_name = match (package_name, matches.get_one::<String>("metadata")) {
+        (Some(name), _) => name,
+        (None, Some(json)) => {
+            serde_json::from_str::<cbp::PackageMetadata>(json)
+                .map_err(|e| anyhow::anyhow!("Invalid --metadata: {}", e))?
+                .name
+        }
+        // vcpkg lists are named `{port}_{version}_{triplet}.list`
+        (None, None) if is_vcpkg => std::path::Path::new(first_source)
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.split('_').next())
+            .unwrap_or("output")
+            .to_string(),
+        (None, None) => archive_package_name(&output, extension),
+    };
+    for path in cbp::copy_licenses(&license_files, temp_dir.path(), &pkg_name)? {
+        println!("-> License {}", path);
+    }
+
+    // Config files point at the cbp home once installed
+    let mut build_prefixes: Vec<std::path::PathBuf> = matches
+        .get_many::<String>("prefix")
+        .into_iter()
+        .flatten()
+        .map(std::path::PathBuf::from)
+        .collect();
+    if is_vcpkg {
+        // vcpkg lists start with the triplet directory under `installed/`
+        let triplets: std::collections::BTreeSet<&str> = file_list
+            .iter()
+            .filter_map(|l| l.split('/').next())
+            .collect();
+        build_prefixes.extend(triplets.iter().map(|t| base_dir.join(t)));
+    }
+    for path in cbp::relocate_configs(temp_dir.path(), &absolute_paths(&build_prefixes))?
+    {
+        println!("-> Relocated {}", path);
+    }
+
+    // Strip binaries, keeping debug info aside if requested
+    let split_debug = matches.get_flag("split-debug");
+    let debug_dir = tempfile::Builder::new().prefix("cbp-debug-").tempdir()?;
+    if matches.get_flag("strip") || split_debug {
+        let stripped =
+            cbp::strip_binaries(temp_dir.path(), split_debug.then(|| debug_dir.path()))?;
+        for (path, before, after) in &stripped {
+            println!(
+                "-> Stripped {} ({} -> {})",
+                path,
+                cbp::format_size(*before),
+                cbp::format_size(*after)
+            );
         }
     }
 
     // Create archive
     let temp_path = temp_dir.path().display().to_string();
+    let metadata_args: Vec<String> = matches
+        .get_one::<String>("metadata")
+        .map(|m| vec!["--metadata".to_string(), m.to_string()])
+        .unwrap_or_default();
+    // Borrowed, as `run_cmd!` consumes its `$[...]` arguments
+    let metadata_args = &metadata_args;
     run_cmd!(
-        ${cbp} tar ${temp_path} -o ${output}
+        ${cbp} tar ${temp_path} -o ${output} --compression ${compression} $[metadata_args]
     )?;
 
+    if !cbp::find_files(debug_dir.path(), None)?.is_empty() {
+        let debug_path = debug_dir.path().display().to_string();
+        let debug_output = cbp::debug_archive_name(&output);
+        run_cmd!(
+            ${cbp} tar ${debug_path} -o ${debug_output} --compression ${compression} $[metadata_args]
+        )?;
+    }
+
     Ok(())
 }
 
-/// Check if a file should be skipped based on ignore patterns
-fn should_skip_file(line: &str, ignore_patterns: &[String]) -> bool {
-    ignore_patterns.iter().any(|pattern| line.contains(pattern))
+/// Package name of an archive named `{name}.{os}.{ext}`, e.g. `python3.11`
+/// for `python3.11.linux.tar.gz`
+fn archive_package_name(output: &str, extension: &str) -> String {
+    let file_name = std::path::Path::new(output)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("output");
+    let stem = file_name
+        .strip_suffix(extension)
+        .and_then(|s| s.strip_suffix('.'))
+        .unwrap_or(file_name);
+    match stem.rsplit_once('.') {
+        Some((name, os)) if ["linux", "macos", "windows", "font"].contains(&os) => {
+            name.to_string()
+        }
+        _ => stem.to_string(),
+    }
+}
+
+/// Check if a file should be skipped based on ignore and include patterns
+///
+/// Patterns are checked against both the source path and the path in the package.
+fn should_skip_file(
+    paths: &[&str],
+    ignore_patterns: &[cbp::PathPattern],
+    include_patterns: &[cbp::PathPattern],
+) -> bool {
+    let matches = |pattern: &cbp::PathPattern| paths.iter().any(|p| pattern.matches(p));
+    ignore_patterns.iter().any(matches)
+        || (!include_patterns.is_empty() && !include_patterns.iter().any(matches))
+}
+
+/// Absolute forms of the paths, both as given and resolved
+fn absolute_paths(paths: &[std::path::PathBuf]) -> Vec<String> {
+    let mut result = vec![];
+    for path in paths {
+        let resolved = dunce::canonicalize(path).ok();
+        for path in std::iter::once(path).chain(resolved.as_ref()) {
+            let path = path.display().to_string();
+            if std::path::Path::new(&path).is_absolute() && !result.contains(&path) {
+                result.push(path);
+            }
+        }
+    }
+    result
+}
+
+/// A file to be collected
+struct Entry {
+    src_path: std::path::PathBuf,
+    /// Source path as given, less the vcpkg triplet, matched by `--ignore` and
+    /// `--include`
+    line: String,
+    /// Path inside the package
+    relative_path: String,
+    /// Executables, made runnable
+    is_bin: bool,
+}
+
+/// Files selected by the `collect` section of a package JSON
+fn layout_entries(layout: &cbp::Collect) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for (dir, patterns) in layout.layout() {
+        for pattern in patterns {
+            let paths = glob::glob(pattern)
+                .map_err(|e| anyhow::anyhow!("Invalid glob {}: {}", pattern, e))?
+                .collect::<Result<Vec<_>, _>>()?;
+            if paths.is_empty() {
+                warn!("No files match {}", pattern);
+            }
+
+            for path in paths {
+                let line = path.to_string_lossy().replace('\\', "/");
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if path.is_dir() && !path.is_symlink() {
+                    // Directories keep their contents under their own name
+                    for file in cbp::find_files(&path, None)? {
+                        entries.push(Entry {
+                            src_path: path.join(&file),
+                            line: format!("{}/{}", line, file),
+                            relative_path: format!("{}/{}/{}", dir, name, file),
+                            is_bin: dir == "bin",
+                        });
+                    }
+                } else {
+                    entries.push(Entry {
+                        relative_path: format!("{}/{}", dir, name),
+                        src_path: path,
+                        line,
+                        is_bin: dir == "bin",
+                    });
+                }
+            }
+        }
+    }
+    Ok(entries)
 }
 /// Split a file path line into parts, optionally skipping the first vcpkg component
 fn get_path_parts(line: &str, is_vcpkg: bool) -> Option<Vec<String>> {
@@ -285,10 +597,56 @@ fn get_relative_path(parts: &[String], mode: &str, is_vcpkg: bool) -> String {
     }
 }
 
-/// Create file copies for alias mappings defined in the copy map
+/// Symlink target to keep, if `src_path` is a relative symlink that stays
+/// inside the package when placed at `relative_path`
+fn package_link(
+    src_path: &std::path::Path,
+    relative_path: &str,
+) -> anyhow::Result<Option<std::path::PathBuf>> {
+    if !cfg!(unix) || !src_path.is_symlink() {
+        return Ok(None);
+    }
+    let target = std::fs::read_link(src_path)?;
+
+    // Walk the target from the link's directory, never above the package root
+    let mut depth = relative_path.split('/').count() as i64 - 1;
+    for component in target.components() {
+        match component {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                depth -= 1;
+                if depth < 0 {
+                    return Ok(None);
+                }
+            }
+            // Absolute targets point outside the package
+            _ => return Ok(None),
+        }
+    }
+    Ok(Some(target))
+}
+
+/// Create a symlink, replacing any existing file
+fn create_symlink(
+    target: &std::path::Path,
+    link: &std::path::Path,
+) -> anyhow::Result<()> {
+    if link.symlink_metadata().is_ok() {
+        std::fs::remove_file(link)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(not(unix))]
+    std::fs::copy(link.with_file_name(target), link)?;
+    Ok(())
+}
+
+/// Create file copies, or symlinks, for alias mappings defined in the copy map
 fn process_file_aliases(
     dest_path: &std::path::Path,
     copy_map: &std::collections::HashMap<String, Vec<String>>,
+    symlink: bool,
 ) -> anyhow::Result<()> {
     if let Some(file_name) = dest_path.file_name().and_then(|n| n.to_str()) {
         if let Some(aliases) = copy_map.get(file_name) {
@@ -299,7 +657,14 @@ fn process_file_aliases(
                 )
             })?;
             for alias in aliases {
-                std::fs::copy(dest_path, parent.join(alias)).map_err(|e| {
+                let result = if symlink {
+                    create_symlink(std::path::Path::new(file_name), &parent.join(alias))
+                } else {
+                    std::fs::copy(dest_path, parent.join(alias))
+                        .map(|_| ())
+                        .map_err(Into::into)
+                };
+                result.map_err(|e| {
                     anyhow::anyhow!("Failed to create alias {}: {}", alias, e)
                 })?;
             }
@@ -307,40 +672,3 @@ fn process_file_aliases(
     }
     Ok(())
 }
-
-/// Check if a file is a Windows PE executable (not a DLL)
-fn is_windows_executable(path: &std::path::Path) -> std::io::Result<bool> {
-    let mut file = std::fs::File::open(path)?;
-    let mut buffer = [0u8; 0x40];
-
-    if file.read_exact(&mut buffer).is_ok() {
-        // Check DOS MZ signature (0x4D5A)
-        // Returns false if not a PE file
-        if buffer[0..2] != [0x4D, 0x5A] {
-            return Ok(false);
-        }
-
-        // Get PE header offset from DOS header at 0x3C
-        // and read PE header (24 bytes)
-        let pe_offset =
-            u32::from_le_bytes(buffer[0x3C..0x40].try_into().unwrap()) as u64;
-        let mut pe_header = [0u8; 24];
-        file.seek(SeekFrom::Start(pe_offset))?;
-        if file.read_exact(&mut pe_header).is_err() {
-            return Ok(false);
-        }
-
-        // Verify PE signature ("PE\0\0" = 0x50450000)
-        if pe_header[0..4] != [0x50, 0x45, 0x00, 0x00] {
-            return Ok(false);
-        }
-
-        // Get characteristics from PE header (offset 22)
-        // IMAGE_FILE_DLL = 0x2000
-        // Returns true if not a DLL
-        let characteristics = u16::from_le_bytes(pe_header[22..24].try_into().unwrap());
-        Ok((characteristics & 0x2000) == 0)
-    } else {
-        Ok(false)
-    }
-}
@@ -0,0 +1,268 @@
+use clap::*;
+use std::path::Path;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> Command {
+    Command::new("doctor")
+        .about("Check the health of the cbp installation")
+        .after_help(include_str!("../../docs/help/doctor.md"))
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// Outcome of a single check: problems found and how to fix them
+struct Finding {
+    problems: Vec<String>,
+    fix: String,
+}
+
+/// Execute doctor command
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+
+    println!(
+        "==> Checking cbp installation in {}",
+        cbp_dirs.home.display()
+    );
+
+    let checks: Vec<(&str, Option<Finding>)> = vec![
+        ("bin directory is on PATH", check_path(&cbp_dirs)?),
+        (
+            "installed cbp matches this version",
+            check_version(&cbp_dirs)?,
+        ),
+        (
+            "records reference existing files",
+            check_records(&cbp_dirs)?,
+        ),
+        ("no unmanaged files", check_unmanaged(&cbp_dirs)?),
+        ("no incomplete downloads in cache", check_cache(&cbp_dirs)?),
+        ("dynamic libraries resolve", check_libs(&cbp_dirs)?),
+        (
+            "shell config contains the init block",
+            check_shell_rc(&cbp_dirs)?,
+        ),
+    ];
+
+    let mut failed = 0;
+    for (name, finding) in &checks {
+        match finding {
+            None => println!("[OK]   {}", name),
+            Some(finding) => {
+                failed += 1;
+                println!("[FAIL] {}", name);
+                for problem in &finding.problems {
+                    println!("    - {}", problem);
+                }
+                println!("    Fix: {}", finding.fix);
+            }
+        }
+    }
+
+    println!();
+    if failed > 0 {
+        println!("==> {} of {} checks failed", failed, checks.len());
+        std::process::exit(1);
+    }
+    println!("==> All checks passed");
+
+    Ok(())
+}
+
+/// Check that the bin directory is listed in PATH
+fn check_path(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let bin = dunce::canonicalize(&cbp_dirs.bin)?;
+    let on_path = std::env::var_os("PATH")
+        .map(|path| {
+            std::env::split_paths(&path)
+                .filter_map(|p| dunce::canonicalize(p).ok())
+                .any(|p| p == bin)
+        })
+        .unwrap_or(false);
+
+    Ok((!on_path).then(|| Finding {
+        problems: vec![format!("{} is not in PATH", cbp_dirs.bin.display())],
+        fix: "run `cbp init` and restart the terminal".to_string(),
+    }))
+}
+
+/// Check that the installed cbp binary reports the running version
+fn check_version(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let exe = cbp_dirs
+        .bin
+        .join(if cfg!(windows) { "cbp.exe" } else { "cbp" });
+    let fix = "run `cbp init` with the latest cbp binary".to_string();
+    if !exe.exists() {
+        return Ok(Some(Finding {
+            problems: vec![format!("{} not found", exe.display())],
+            fix,
+        }));
+    }
+
+    let expected = format!("cbp {}", crate_version!());
+    let problem = match std::process::Command::new(&exe).arg("--version").output() {
+        Ok(output) => {
+            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if version == expected {
+                return Ok(None);
+            }
+            format!(
+                "{} reports '{}', expected '{}'",
+                exe.display(),
+                version,
+                expected
+            )
+        }
+        Err(e) => format!("{} cannot be executed: {}", exe.display(), e),
+    };
+
+    Ok(Some(Finding {
+        problems: vec![problem],
+        fix,
+    }))
+}
+
+/// Check that every file listed in package records exists
+fn check_records(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let mut problems = Vec::new();
+    let mut broken = Vec::new();
+    for pkg in cbp_dirs.installed_packages()? {
+        let missing = cbp_dirs
+            .package_files(&pkg)?
+            .into_iter()
+            .filter(|f| !f.ends_with('/'))
+            .filter(|f| {
+                let path = cbp_dirs.home.join(f);
+                !path.exists() && !path.is_symlink()
+            })
+            .count();
+        if missing > 0 {
+            problems.push(format!("{}: {} missing files", pkg, missing));
+            broken.push(pkg);
+        }
+    }
+
+    Ok((!problems.is_empty()).then(|| Finding {
+        problems,
+        fix: format!(
+            "reinstall with `cbp remove {0} && cbp install {0}`",
+            broken.join(" ")
+        ),
+    }))
+}
+
+/// Check for files not managed by any package
+fn check_unmanaged(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let files = cbp_dirs.unmanaged_files()?;
+
+    Ok((!files.is_empty()).then(|| Finding {
+        problems: files,
+        fix: "review with `cbp check` and remove files you no longer need".to_string(),
+    }))
+}
+
+/// Check for interrupted downloads left in the cache
+fn check_cache(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let files = cbp::find_files(&cbp_dirs.cache, Some("*.incomplete"))?;
+
+    Ok((!files.is_empty()).then(|| Finding {
+        problems: files.iter().map(|f| format!("cache/{}", f)).collect(),
+        fix: format!("remove them from {}", cbp_dirs.cache.display()),
+    }))
+}
+
+/// Check that shared libraries in lib/ resolve, and so do the libraries they need
+///
+/// Needed libraries are read from the binaries and looked up in lib/ and among
+/// the libraries every system provides.
+fn check_libs(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let lib_dir = cbp_dirs.home.join("lib");
+    if !lib_dir.exists() {
+        return Ok(None);
+    }
+    let os_type = cbp::get_os_type()?;
+
+    let mut problems = vec![];
+    for file in cbp::find_files(&lib_dir, None)? {
+        let path = lib_dir.join(&file);
+        if !is_dynamic_lib(&path) {
+            continue;
+        }
+        if path.is_symlink() {
+            if !path.exists() {
+                problems.push(format!("lib/{} is a broken symlink", file));
+            }
+            // The target is checked on its own
+            continue;
+        }
+
+        let Some(info) = cbp::inspect_binary(&std::fs::read(&path)?) else {
+            continue;
+        };
+        for lib in &info.needed {
+            let name = lib.rsplit(['/', '\\']).next().unwrap_or(lib);
+            let resolved = cbp::is_system_library(&os_type, lib)
+                || lib_dir.join(name).exists()
+                || (Path::new(lib).is_absolute() && Path::new(lib).exists());
+            if !resolved {
+                problems.push(format!("lib/{} needs {}, which is not found", file, lib));
+            }
+        }
+    }
+
+    Ok((!problems.is_empty()).then(|| Finding {
+        problems,
+        fix: "reinstall the packages providing these libraries".to_string(),
+    }))
+}
+
+/// Check if a file name looks like a shared library
+fn is_dynamic_lib(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.ends_with(".so")
+        || name.contains(".so.")
+        || name.ends_with(".dylib")
+        || name.ends_with(".dll")
+}
+
+/// Check that at least one shell config file contains the cbp init block
+#[cfg(unix)]
+fn check_shell_rc(cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+
+    let mut found = false;
+    for rc in super::init::SHELL_RCS {
+        let rc_path = home.join(rc);
+        if rc_path.exists()
+            && std::fs::read_to_string(&rc_path)?
+                .lines()
+                .any(|line| line.trim() == "# .cbp start")
+        {
+            found = true;
+        }
+    }
+
+    Ok((!found).then(|| Finding {
+        problems: vec![format!(
+            "none of {} contains the `# .cbp start` block",
+            super::init::SHELL_RCS.join(", ")
+        )],
+        fix: format!("run `cbp init {}`", cbp_dirs.home.display()),
+    }))
+}
+
+#[cfg(not(unix))]
+fn check_shell_rc(_cbp_dirs: &cbp::CbpDirs) -> anyhow::Result<Option<Finding>> {
+    Ok(None)
+}
@@ -33,18 +33,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     // Set up HTTP agent
     let agent = cbp::create_http_agent(opt_proxy_url)?;
 
-    // Build JSON file URL
-    let raw_url = cbp::github_raw_url();
-    let json_url = format!("{}/wang-q/cbp/master/packages/{}.json", raw_url, package);
-
-    // Get JSON data
-    let resp = agent
-        .get(&json_url)
-        .set("user-agent", "cbp")
-        .call()?
-        .into_string()?;
-
-    let info: serde_json::Value = serde_json::from_str(&resp)?;
+    let info = cbp::fetch_package_json(&agent, package)?;
 
     if is_json {
         // Output formatted JSON
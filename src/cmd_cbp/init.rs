@@ -1,6 +1,10 @@
 use std::fs;
 use std::path::Path;
 
+/// Shell config files updated with the PATH block
+#[cfg(unix)]
+pub const SHELL_RCS: [&str; 3] = [".bashrc", ".bash_profile", ".zshrc"];
+
 pub fn make_subcommand() -> clap::Command {
     clap::Command::new("init")
         .about("Initialize cbp environment")
@@ -61,10 +65,9 @@ pub fn execute(matches: &clap::ArgMatches) -> anyhow::Result<()> {
     // Update PATH in shell config files
     #[cfg(unix)]
     {
-        let shell_rcs = vec![".bashrc", ".bash_profile", ".zshrc"];
         let home = dirs::home_dir()
             .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-        for rc in shell_rcs {
+        for rc in SHELL_RCS {
             let rc_path = home.join(rc);
             if rc_path.exists() {
                 update_shell_rc(&rc_path, &cbp_dirs.bin)?;
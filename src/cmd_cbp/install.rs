@@ -1,4 +1,5 @@
 use clap::*;
+use std::collections::BTreeMap;
 
 pub fn make_subcommand() -> Command {
     Command::new("install")
@@ -27,6 +28,12 @@ pub fn make_subcommand() -> Command {
                 .value_name("TYPE")
                 .value_parser(["macos", "linux", "windows", "font"]),
         )
+        .arg(
+            Arg::new("deps")
+                .long("deps")
+                .help("Also install dependencies listed in the package definitions")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("dir")
                 .long("dir")
@@ -42,7 +49,11 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     //----------------------------
     // Args
     //----------------------------
-    let packages = args.get_many::<String>("packages").unwrap();
+    let packages: Vec<String> = args
+        .get_many::<String>("packages")
+        .unwrap()
+        .cloned()
+        .collect();
 
     // Set up HTTP agent with optional proxy
     let opt_proxy_url = args.get_one::<String>("proxy");
@@ -56,41 +67,51 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         .map(|s| s.as_str())
         .unwrap_or(&os_type);
 
+    //----------------------------
+    // Dependencies
+    //----------------------------
+    // Installation order with dependencies before their dependents
+    let mut order = Vec::new();
+    let mut deps_map = BTreeMap::new();
+    if args.get_flag("deps") {
+        for pkg in &packages {
+            resolve_deps(&agent, pkg, &mut order, &mut deps_map)?;
+        }
+    } else {
+        order = packages.clone();
+    }
+
     //----------------------------
     // Processing
     //----------------------------
-    for pkg in packages {
+    for pkg in &order {
+        let explicit = packages.contains(pkg);
+
         // Check if already installed
         let record_file = cbp_dirs.records.join(format!("{}.files", pkg));
         if record_file.exists() {
             println!("==> Package {} is already installed", pkg);
+
+            // Promote a dependency install that is now requested explicitly
+            let mut record = cbp_dirs.read_record(pkg)?;
+            if explicit && !record.explicit {
+                record.explicit = true;
+                cbp_dirs.write_record(&record)?;
+                println!("    Marked as explicitly installed");
+            }
             continue;
         }
 
         // Download package
         println!("==> Downloading {}", pkg);
-        let pkg_file = format!("{}.{}.tar.gz", pkg, pkg_type);
-        let temp_file = cbp_dirs.cache.join(format!("{}.incomplete", pkg_file));
-        let cache_file = cbp_dirs.cache.join(&pkg_file);
-
-        // Create cache directory if needed
-        std::fs::create_dir_all(&cbp_dirs.cache)?;
-
-        // Download from GitHub
-        let base_url = cbp::github_release_url();
-        let url = format!(
-            "{}/wang-q/cbp/releases/download/Binaries/{}",
-            base_url, pkg_file
-        );
-        let mut file = std::fs::File::create(&temp_file)?;
-        let resp = agent.get(&url).call()?;
-        std::io::copy(&mut resp.into_reader(), &mut file)?;
-
-        // Move to final location using move_file_or_dir to handle cross-device scenarios
-        cbp::move_file_or_dir(&temp_file, &cache_file)?;
+        let cache_file = cbp_dirs.download_package(pkg, pkg_type, &agent)?;
 
         // Install package
         cbp_dirs.install_package(pkg, &cache_file)?;
+        let mut record = cbp_dirs.read_record(pkg)?;
+        record.explicit = explicit;
+        record.dependencies = deps_map.get(pkg).cloned().unwrap_or_default();
+        cbp_dirs.write_record(&record)?;
         println!("==> Successfully installed {}", pkg);
     }
 
@@ -105,3 +126,35 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Resolve dependencies of a package from its definition, depth first
+///
+/// Packages are appended to `order` after all of their dependencies.
+fn resolve_deps(
+    agent: &ureq::Agent,
+    pkg: &str,
+    order: &mut Vec<String>,
+    deps_map: &mut BTreeMap<String, Vec<String>>,
+) -> anyhow::Result<()> {
+    if deps_map.contains_key(pkg) {
+        return Ok(());
+    }
+
+    let json = cbp::fetch_package_json(agent, pkg)?;
+    let deps: Vec<String> = json["dependencies"]
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    deps_map.insert(pkg.to_string(), deps.clone());
+
+    for dep in &deps {
+        resolve_deps(agent, dep, order, deps_map)?;
+    }
+    order.push(pkg.to_string());
+
+    Ok(())
+}
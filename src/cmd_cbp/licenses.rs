@@ -0,0 +1,171 @@
+use clap::*;
+use serde::Serialize;
+use std::io::Write;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> Command {
+    Command::new("licenses")
+        .about("Report licenses of installed packages")
+        .after_help(include_str!("../../docs/help/licenses.md"))
+        .arg(
+            Arg::new("packages")
+                .help("Name of the packages, defaults to all installed ones")
+                .num_args(0..)
+                .index(1)
+                .value_name("PACKAGES"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output as JSON")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("csv")
+                .long("csv")
+                .help("Output as CSV")
+                .conflicts_with("json")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Output file path, stdout for the screen")
+                .num_args(1)
+                .default_value("stdout")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// License of one installed package
+#[derive(Debug, Serialize)]
+struct License {
+    name: String,
+    version: Option<String>,
+    /// SPDX identifier from the package metadata or the package JSON
+    license: String,
+    /// License texts, relative to the cbp home
+    files: Vec<String>,
+}
+
+/// Execute licenses command
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+    let mut writer = cbp::writer(args.get_one::<String>("output").unwrap())?;
+    let agent = cbp::create_http_agent(args.get_one::<String>("proxy"))?;
+
+    let mut packages: Vec<String> = match args.get_many::<String>("packages") {
+        Some(pkgs) => pkgs.cloned().collect(),
+        None => cbp_dirs.installed_packages()?,
+    };
+    packages.sort();
+
+    //----------------------------
+    // Operating
+    //----------------------------
+    let mut licenses = vec![];
+    for pkg in &packages {
+        let record_file = cbp_dirs.records.join(format!("{}.files", pkg));
+        if !record_file.exists() {
+            return Err(anyhow::anyhow!("Package {} is not installed", pkg));
+        }
+
+        // Files gathered by `collect` into share/licenses/{pkg}/
+        let prefix = format!("share/licenses/{}/", pkg);
+        let files = std::fs::read_to_string(&record_file)?
+            .lines()
+            .filter(|line| line.starts_with(&prefix) && !line.ends_with('/'))
+            .map(str::to_string)
+            .collect();
+
+        // Packages installed without metadata fall back to the package JSON
+        let metadata = cbp_dirs.read_record(pkg)?.metadata;
+        let license = metadata
+            .as_ref()
+            .and_then(|m| m.license.clone())
+            .or_else(|| package_license(&agent, pkg))
+            .unwrap_or_else(|| "NOASSERTION".to_string());
+        licenses.push(License {
+            name: pkg.to_string(),
+            version: metadata.as_ref().map(|m| m.version.clone()),
+            license,
+            files,
+        });
+    }
+
+    if args.get_flag("json") {
+        writeln!(writer, "{}", serde_json::to_string_pretty(&licenses)?)?;
+    } else if args.get_flag("csv") {
+        writeln!(writer, "name,version,license,files")?;
+        for license in &licenses {
+            let fields = [
+                license.name.as_str(),
+                license.version.as_deref().unwrap_or(""),
+                license.license.as_str(),
+                &license.files.join(";"),
+            ];
+            let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+    } else {
+        writeln!(writer, "==> Licenses of installed packages:")?;
+        for license in &licenses {
+            writeln!(
+                writer,
+                "    {} {}: {}",
+                license.name,
+                license.version.as_deref().unwrap_or("-"),
+                license.license
+            )?;
+            if license.files.is_empty() {
+                writeln!(writer, "        (no license file)")?;
+            }
+            for file in &license.files {
+                writeln!(writer, "        {}", file)?;
+            }
+        }
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Read the `license` field of a package JSON from the cbp repository
+fn package_license(agent: &ureq::Agent, pkg: &str) -> Option<String> {
+    match cbp::fetch_package_json(agent, pkg) {
+        Ok(json) => json["license"].as_str().map(str::to_string),
+        Err(e) => {
+            tracing::debug!("Failed to fetch package JSON of {}: {}", pkg, e);
+            None
+        }
+    }
+}
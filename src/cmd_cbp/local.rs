@@ -53,17 +53,25 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     // Process packages
     for pkg in args.get_many::<String>("packages").unwrap() {
-        // Try local binaries directory first
-        let local_file = std::path::Path::new("binaries")
-            .join(format!("{}.{}.tar.gz", pkg, pkg_type));
+        // Try local binaries directory first, then the cache directory,
+        // with any supported compression
+        let names: Vec<String> = cbp::CompressionFormat::ALL
+            .iter()
+            .map(|c| format!("{}.{}.{}", pkg, pkg_type, c.extension()))
+            .collect();
+        let local_file = names
+            .iter()
+            .map(|name| std::path::Path::new("binaries").join(name))
+            .find(|path| path.exists());
+        let cache_file = names
+            .iter()
+            .map(|name| cbp_dirs.cache.join(name))
+            .find(|path| path.exists());
 
-        // Then try cache directory
-        let cache_file = cbp_dirs.cache.join(format!("{}.{}.tar.gz", pkg, pkg_type));
-
-        let pkg_file = if local_file.exists() {
+        let pkg_file = if let Some(local_file) = local_file {
             println!("==> Using locally built package from binaries/");
             local_file
-        } else if cache_file.exists() {
+        } else if let Some(cache_file) = cache_file {
             println!("==> Using cached package from ~/.cbp/cache/");
             cache_file
         } else {
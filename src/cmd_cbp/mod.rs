@@ -1,16 +1,21 @@
 //! Subcommand modules for the `cbp` binary.
 
+pub mod autoremove;
 pub mod avail;
 pub mod build;
 pub mod check;
 pub mod collect;
+pub mod doctor;
 pub mod dot;
 pub mod info;
 pub mod init;
 pub mod install;
+pub mod licenses;
 pub mod list;
 pub mod local;
 pub mod prefix;
 pub mod remove;
+pub mod run;
+pub mod self_update;
 pub mod snap;
 pub mod tar;
@@ -12,6 +12,27 @@ pub fn make_subcommand() -> Command {
                 .index(1)
                 .value_name("PACKAGES"),
         )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .help("Show what would be removed without removing anything")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .short('r')
+                .help("Also remove dependencies that are no longer needed")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Remove packages even if other packages depend on them")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("dir")
                 .long("dir")
@@ -25,47 +46,68 @@ pub fn make_subcommand() -> Command {
 
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+    let dry_run = args.get_flag("dry-run");
+    let opt_force = args.get_flag("force");
 
+    let mut packages: Vec<String> = Vec::new();
     for package in args.get_many::<String>("packages").unwrap() {
         let file_path = cbp_dirs.records.join(format!("{}.files", package));
         if !file_path.exists() {
             println!("==> Package {} is not installed", package);
             continue;
         }
+        packages.push(package.to_string());
+    }
 
-        println!("==> Removing {}:", package);
-        let content = std::fs::read_to_string(&file_path)?;
-
-        for line in content.lines() {
-            if line.is_empty() {
-                continue;
-            }
-
-            let file = cbp_dirs.home.join(line);
-            if !file.exists() && !file.is_symlink() {
-                println!("    File not found: {}", file.display());
-                continue;
+    // Refuse to break packages that depend on the removed ones
+    if !opt_force {
+        let mut kept = Vec::new();
+        for package in &packages {
+            let dependents: Vec<String> = cbp_dirs
+                .dependents(package)?
+                .into_iter()
+                .filter(|d| !packages.contains(d))
+                .collect();
+            if dependents.is_empty() {
+                kept.push(package.to_string());
+            } else {
+                println!(
+                    "==> Package {} is required by: {}",
+                    package,
+                    dependents.join(", ")
+                );
+                println!("    Skipped, use --force to remove anyway");
             }
+        }
+        packages = kept;
+    }
 
-            if !file.is_file() && !file.is_symlink() {
-                continue;
+    // Dependencies left unused by the removal
+    if args.get_flag("recursive") {
+        let mut deps = Vec::new();
+        let mut stack = packages.clone();
+        while let Some(package) = stack.pop() {
+            for dep in cbp_dirs.read_record(&package)?.dependencies {
+                if !deps.contains(&dep) {
+                    deps.push(dep.clone());
+                    stack.push(dep);
+                }
             }
-
-            std::fs::remove_file(&file)?;
-
-            // Handle resource fork files
-            let file_name = match file.file_name().and_then(|n| n.to_str()) {
-                Some(name) => name,
-                None => continue,
-            };
-
-            let resource_fork = file.parent().unwrap().join(format!("._{}", file_name));
-            if resource_fork.exists() {
-                std::fs::remove_file(&resource_fork)?;
+        }
+        for orphan in cbp_dirs.orphan_packages(&packages)? {
+            if deps.contains(&orphan) {
+                packages.push(orphan);
             }
         }
+    }
 
-        std::fs::remove_file(&file_path)?;
+    for package in &packages {
+        if dry_run {
+            println!("==> Would remove {}:", package);
+        } else {
+            println!("==> Removing {}:", package);
+        }
+        cbp_dirs.remove_package(package, &packages, dry_run)?;
         println!("    Done");
     }
 
@@ -0,0 +1,151 @@
+use anyhow::Context;
+use clap::*;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> Command {
+    Command::new("run")
+        .visible_alias("exec")
+        .about("Run a package binary without installing it")
+        .after_help(include_str!("../../docs/help/run.md"))
+        .trailing_var_arg(true)
+        .arg(
+            Arg::new("package")
+                .help("Package name")
+                .required(true)
+                .index(1)
+                .value_name("PACKAGE"),
+        )
+        .arg(
+            Arg::new("args")
+                .help("Arguments passed to the binary")
+                .num_args(0..)
+                .index(2)
+                .allow_hyphen_values(true)
+                .value_name("ARGS"),
+        )
+        .arg(
+            Arg::new("bin")
+                .long("bin")
+                .short('b')
+                .help("Binary to run (default: the package name)")
+                .num_args(1)
+                .value_name("BIN"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .short('d')
+                .num_args(1)
+                .value_name("DIR")
+                .help("Change working directory")
+                .hide(true),
+        )
+}
+
+/// Execute run command
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let pkg = args.get_one::<String>("package").unwrap();
+    let bin = args
+        .get_one::<String>("bin")
+        .map(|s| s.as_str())
+        .unwrap_or(pkg);
+    let bin_args: Vec<String> = args
+        .get_many::<String>("args")
+        .map(|a| a.cloned().collect())
+        .unwrap_or_default();
+
+    let cbp_dirs = cbp::CbpDirs::from_arg_matches(args)?;
+    let os_type = cbp::get_os_type()?;
+
+    //----------------------------
+    // Prepare prefix
+    //----------------------------
+    // Status messages go to stderr, stdout belongs to the executed binary
+    let record_file = cbp_dirs.records.join(format!("{}.files", pkg));
+    let (prefix, temp_guard) = if record_file.exists() {
+        (cbp_dirs.home.clone(), None)
+    } else {
+        let cache_file = cbp_dirs.cache.join(format!("{}.{}.tar.gz", pkg, os_type));
+        let pkg_file = if cache_file.exists() {
+            cache_file
+        } else {
+            eprintln!("==> Downloading {}", pkg);
+            let agent = cbp::create_http_agent(args.get_one::<String>("proxy"))?;
+            cbp_dirs.download_package(pkg, &os_type, &agent)?
+        };
+
+        // Unpack into a temporary prefix that is removed after the run
+        let temp_dir = tempfile::Builder::new().prefix("cbp-run-").tempdir()?;
+        let temp_dirs = cbp::CbpDirs::from(temp_dir.path().to_path_buf())?;
+        temp_dirs.extract_package(pkg, &pkg_file)?;
+        (temp_dirs.home, Some(temp_dir))
+    };
+
+    let bin_dir = prefix.join("bin");
+    let bin_path = cbp::resolve_bin_path(&bin_dir, bin);
+    if !bin_path.exists() {
+        return Err(anyhow::anyhow!(
+            "Binary {} not found in package {}",
+            bin,
+            pkg
+        ));
+    }
+
+    //----------------------------
+    // Environment
+    //----------------------------
+    let mut paths = vec![bin_dir.clone()];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    let path_env = std::env::join_paths(paths)?;
+
+    let lib_var = match os_type.as_str() {
+        "linux" => Some("LD_LIBRARY_PATH"),
+        "macos" => Some("DYLD_LIBRARY_PATH"),
+        _ => None,
+    };
+
+    let mut cmd = if bin_path.extension().is_some_and(|ext| ext == "ps1") {
+        let mut cmd = std::process::Command::new("powershell");
+        cmd.args(["-NoProfile", "-ExecutionPolicy", "Bypass", "-File"])
+            .arg(&bin_path);
+        cmd
+    } else {
+        std::process::Command::new(&bin_path)
+    };
+    cmd.args(&bin_args).env("PATH", path_env);
+
+    if let Some(lib_var) = lib_var {
+        let mut libs = vec![prefix.join("lib")];
+        if let Some(path) = std::env::var_os(lib_var) {
+            libs.extend(std::env::split_paths(&path));
+        }
+        cmd.env(lib_var, std::env::join_paths(libs)?);
+    }
+
+    //----------------------------
+    // Execute
+    //----------------------------
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to execute {}", bin_path.display()))?;
+
+    // Remove the temporary prefix before exiting with the binary's status
+    drop(temp_guard);
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
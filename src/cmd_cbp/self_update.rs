@@ -0,0 +1,209 @@
+use anyhow::Context;
+use clap::*;
+use std::path::Path;
+
+/// Create clap subcommand arguments
+pub fn make_subcommand() -> Command {
+    Command::new("self-update")
+        .about("Update cbp to the latest release")
+        .after_help(include_str!("../../docs/help/self_update.md"))
+        .arg(
+            Arg::new("repo")
+                .long("repo")
+                .help("GitHub repository to fetch releases from")
+                .num_args(1)
+                .value_name("OWNER/REPO")
+                .default_value("wang-q/cbp"),
+        )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Only check for a newer version")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Reinstall even if already up to date")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .help("Proxy server URL (e.g., socks5://127.0.0.1:7890)")
+                .num_args(1)
+                .value_name("URL"),
+        )
+}
+
+/// Execute self-update command
+pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
+    //----------------------------
+    // Args
+    //----------------------------
+    let repo = args.get_one::<String>("repo").unwrap();
+    let opt_check = args.get_flag("check");
+    let opt_force = args.get_flag("force");
+
+    let agent = cbp::create_http_agent(args.get_one::<String>("proxy"))?;
+
+    let current_exe = dunce::canonicalize(std::env::current_exe()?)?;
+    let current_version = crate_version!();
+
+    let os_type = cbp::get_os_type()?;
+    let asset_name = if os_type == "windows" {
+        "cbp.windows.exe".to_string()
+    } else {
+        format!("cbp.{}", os_type)
+    };
+
+    //----------------------------
+    // Latest release
+    //----------------------------
+    let release: serde_json::Value = agent
+        .get(&format!(
+            "{}/repos/{}/releases/latest",
+            cbp::github_api_url(),
+            repo
+        ))
+        .set("user-agent", "cbp")
+        .call()?
+        .into_json()?;
+
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("No tag found in the latest release"))?;
+    let latest_version = tag.trim_start_matches('v');
+
+    println!("==> Current version: {}", current_version);
+    println!("==> Latest version: {}", latest_version);
+
+    let is_newer = cbp::compare_versions(latest_version, current_version)
+        == std::cmp::Ordering::Greater;
+    if opt_check {
+        if is_newer {
+            println!("==> Run `cbp self-update` to update");
+        }
+        return Ok(());
+    }
+    if !is_newer && !opt_force {
+        println!("==> cbp is up to date");
+        return Ok(());
+    }
+
+    let asset = release["assets"]
+        .as_array()
+        .and_then(|assets| {
+            assets
+                .iter()
+                .find(|a| a["name"].as_str() == Some(asset_name.as_str()))
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!("Asset {} not found in release {}", asset_name, tag)
+        })?;
+
+    //----------------------------
+    // Download and verify
+    //----------------------------
+    // Download next to the executable so the final rename stays on one filesystem,
+    // keeping `.exe` so Windows can run it for verification
+    let exe_dir = current_exe
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Cannot get executable directory"))?;
+    let new_exe = tempfile::Builder::new()
+        .prefix(".cbp-update-")
+        .suffix(std::env::consts::EXE_SUFFIX)
+        .tempfile_in(exe_dir)?
+        .into_temp_path();
+
+    let url = format!(
+        "{}/{}/releases/download/{}/{}",
+        cbp::github_release_url(),
+        repo,
+        tag,
+        asset_name
+    );
+    println!("==> Downloading {}", url);
+    let digest = asset["digest"]
+        .as_str()
+        .and_then(|d| d.strip_prefix("sha256:"));
+    let cbp_dirs = cbp::CbpDirs::from_exe()?;
+    cbp::download_file(&[&url], &new_exe, &agent, digest, &cbp_dirs.home)?;
+
+    verify_download(&new_exe, asset, latest_version)?;
+
+    //----------------------------
+    // Replace
+    //----------------------------
+    replace_exe(&new_exe, &current_exe)?;
+    println!(
+        "==> Updated {} to {}",
+        current_exe.display(),
+        latest_version
+    );
+
+    Ok(())
+}
+
+/// Verify size and version of the downloaded executable
+fn verify_download(
+    path: &Path,
+    asset: &serde_json::Value,
+    version: &str,
+) -> anyhow::Result<()> {
+    if let Some(size) = asset["size"].as_u64() {
+        let actual = std::fs::metadata(path)?.len();
+        if actual != size {
+            return Err(anyhow::anyhow!(
+                "Size mismatch: expected {} bytes, got {}",
+                size,
+                actual
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    // The new binary must run on this platform and report the expected version
+    let output = std::process::Command::new(path)
+        .arg("--version")
+        .output()
+        .context("Downloaded binary cannot be executed")?;
+    let reported = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if reported != format!("cbp {}", version) {
+        return Err(anyhow::anyhow!(
+            "Downloaded binary reports '{}', expected 'cbp {}'",
+            reported,
+            version
+        ));
+    }
+
+    Ok(())
+}
+
+/// Replace the executable with the new one
+///
+/// Windows locks running executables, so the old one is renamed aside first.
+fn replace_exe(new_exe: &Path, target: &Path) -> anyhow::Result<()> {
+    if cfg!(windows) {
+        let old = target.with_extension("old.exe");
+        if old.exists() {
+            // Left over from a previous update
+            std::fs::remove_file(&old)?;
+        }
+        std::fs::rename(target, &old)?;
+        if let Err(e) = std::fs::rename(new_exe, target) {
+            std::fs::rename(&old, target)?;
+            return Err(e.into());
+        }
+    } else {
+        std::fs::rename(new_exe, target)?;
+    }
+
+    Ok(())
+}
@@ -44,7 +44,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let comment = read_comment(archive_path)?;
     if comment.is_empty() {
         return Err(anyhow::anyhow!(
-            "Snapshot has no source path information in gzip comment"
+            "Snapshot has no source path information in snapshot comment"
         ));
     }
 
@@ -54,12 +54,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
 
     let mut modified: Vec<(PathBuf, PathBuf)> = Vec::new();
 
-    let file = std::fs::File::open(archive_path)?;
-    let decoder = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(decoder);
+    let mut archive = cbp::open_archive(archive_path)?;
 
     for entry in archive.entries()? {
         let mut entry = entry?;
+        if entry.header().entry_type() == tar::EntryType::XGlobalHeader {
+            continue;
+        }
         let entry_path = entry.path()?.to_path_buf();
 
         let target = find_target_path(&entry_path, &source_paths, &home);
@@ -94,8 +95,11 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     }
 
     if pack {
-        let delta_name = delta_output_name(archive_path);
-        pack_modified(&modified, &comment, &home, &delta_name)?;
+        // The delta keeps the compression of the snapshot
+        let compression =
+            cbp::CompressionFormat::detect(archive_path)?.unwrap_or_default();
+        let delta_name = delta_output_name(archive_path, compression);
+        pack_modified(&modified, &comment, &home, &delta_name, compression)?;
         println!(
             "==> Delta snapshot created: {} ({} files)",
             delta_name,
@@ -113,14 +117,12 @@ fn pack_modified(
     full_comment: &str,
     home: &Path,
     output: &str,
+    compression: cbp::CompressionFormat,
 ) -> anyhow::Result<()> {
     let (source_paths, _) = parse_comment(full_comment);
 
     let tar_file = std::fs::File::create(output)?;
-    let gz = flate2::GzBuilder::new()
-        .comment(full_comment.as_bytes())
-        .write(tar_file, flate2::Compression::default());
-    let mut archive = tar::Builder::new(gz);
+    let mut archive = super::save::create_snapshot(tar_file, compression, full_comment)?;
 
     for (target, display_path) in modified {
         let archive_name =
@@ -137,6 +139,6 @@ fn pack_modified(
         archive.append_path_with_name(target, &archive_name)?;
     }
 
-    archive.finish()?;
+    archive.into_inner()?.finish()?;
     Ok(())
 }
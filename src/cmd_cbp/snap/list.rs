@@ -50,20 +50,21 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
             }
         }
     } else if comment.is_empty() {
-        println!("No source path information in gzip comment");
+        println!("No source path information in snapshot comment");
     }
 
     println!();
     println!("Archive contents:");
 
-    let file = std::fs::File::open(archive_path)?;
-    let decoder = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(decoder);
+    let mut archive = cbp::open_archive(archive_path)?;
 
     let mut count = 0u64;
     let mut total_size = 0u64;
     for entry in archive.entries()? {
         let entry = entry?;
+        if entry.header().entry_type() == tar::EntryType::XGlobalHeader {
+            continue;
+        }
         let path = entry.path()?.to_path_buf();
         let size = entry.header().size()?;
         if verbose {
@@ -55,7 +55,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     let comment = read_comment(archive_path)?;
     if comment.is_empty() {
         return Err(anyhow::anyhow!(
-            "Snapshot has no source path information in gzip comment"
+            "Snapshot has no source path information in snapshot comment"
         ));
     }
 
@@ -67,12 +67,13 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         println!("==> Source paths: {}", source_paths.join(", "));
     }
 
-    let file = std::fs::File::open(archive_path)?;
-    let decoder = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(decoder);
+    let mut archive = cbp::open_archive(archive_path)?;
 
     for entry in archive.entries()? {
         let mut entry = entry?;
+        if entry.header().entry_type() == tar::EntryType::XGlobalHeader {
+            continue;
+        }
         let entry_path = entry.path()?.to_path_buf();
 
         if custom_target.is_some() {
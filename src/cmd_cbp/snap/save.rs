@@ -39,6 +39,16 @@ pub fn make_subcommand() -> Command {
                 .num_args(1)
                 .value_name("PATTERN"),
         )
+        .arg(
+            Arg::new("compression")
+                .long("compression")
+                .short('c')
+                .help("Compression format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(cbp::CompressionFormat::NAMES)
+                .default_value("gzip"),
+        )
 }
 
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
@@ -51,6 +61,9 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         .map(|v| v.cloned().collect())
         .unwrap_or_default();
 
+    let compression: cbp::CompressionFormat =
+        args.get_one::<String>("compression").unwrap().parse()?;
+
     let home = dirs::home_dir().context("Cannot determine HOME directory")?;
 
     let mut source_infos: Vec<(PathBuf, String)> = Vec::new();
@@ -73,7 +86,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_else(|| "archive".to_string());
-            format!("{}.snap.tar.gz", basename)
+            format!("{}.snap.{}", basename, compression.extension())
         }
     };
 
@@ -86,10 +99,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     .to_string();
 
     let tar_file = std::fs::File::create(&output)?;
-    let gz = flate2::GzBuilder::new()
-        .comment(comment.as_bytes())
-        .write(tar_file, flate2::Compression::default());
-    let mut archive = tar::Builder::new(gz);
+    let mut archive = create_snapshot(tar_file, compression, &comment)?;
 
     let mut file_count = 0u64;
 
@@ -134,7 +144,7 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         }
     }
 
-    archive.finish()?;
+    archive.into_inner()?.finish()?;
     println!("==> Snapshot created: {}", output);
     println!("==> Files: {}", file_count);
     let sources: Vec<&str> = source_infos.iter().map(|(_, rel)| rel.as_str()).collect();
@@ -154,3 +164,21 @@ fn is_excluded(path: &Path, patterns: &[String]) -> bool {
             .unwrap_or(false)
     })
 }
+
+/// Start a snapshot tarball carrying `comment`
+///
+/// Gzip keeps the comment in its header, zstd and xz in a pax global header.
+pub fn create_snapshot(
+    file: std::fs::File,
+    compression: cbp::CompressionFormat,
+    comment: &str,
+) -> anyhow::Result<tar::Builder<cbp::CompressedWriter<std::fs::File>>> {
+    let archive = if compression == cbp::CompressionFormat::Gzip {
+        tar::Builder::new(compression.writer(file, Some(comment))?)
+    } else {
+        let mut archive = tar::Builder::new(compression.writer(file, None)?);
+        cbp::append_tar_comment(&mut archive, comment)?;
+        archive
+    };
+    Ok(archive)
+}
@@ -27,8 +27,34 @@ pub fn make_subcommand() -> Command {
                 .num_args(1)
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::new("compression")
+                .long("compression")
+                .short('c')
+                .help("Compression format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(cbp::CompressionFormat::NAMES)
+                .default_value("gzip"),
+        )
+        .arg(
+            Arg::new("metadata")
+                .long("metadata")
+                .help("Package metadata JSON to embed in the archive")
+                .num_args(1)
+                .value_name("JSON"),
+        )
+        .arg(
+            Arg::new("verify-reproducible")
+                .long("verify-reproducible")
+                .help("Build the archive twice and check that both are identical")
+                .action(ArgAction::SetTrue),
+        )
 }
 
+/// Fixed mtime of all entries, 2024-01-01 00:00:00 UTC
+const MTIME: u64 = 1704067200;
+
 pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     //----------------------------
     // Args
@@ -41,19 +67,33 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
         ));
     }
 
+    let compression: cbp::CompressionFormat =
+        args.get_one::<String>("compression").unwrap().parse()?;
+
     let tar_name = args
         .get_one::<String>("outfile")
         .map(|s| s.to_string())
         .unwrap_or_else(|| {
             format!(
-                "{}.tar.gz",
+                "{}.{}",
                 collect_dir
                     .file_name()
                     .map(|n| n.to_string_lossy().into_owned())
-                    .unwrap_or_else(|| "archive".to_string())
+                    .unwrap_or_else(|| "archive".to_string()),
+                compression.extension()
             )
         });
 
+    // Normalized, so the same metadata always gives the same bytes
+    let metadata = match args.get_one::<String>("metadata") {
+        Some(json) => {
+            let metadata: cbp::PackageMetadata = serde_json::from_str(json)
+                .map_err(|e| anyhow::anyhow!("Invalid --metadata: {}", e))?;
+            Some(serde_json::to_string(&metadata)?)
+        }
+        None => None,
+    };
+
     let cleanup = args.get_flag("cleanup");
     let doc_dirs = ["share/info/", "share/man/", "share/doc/", "share/locale/"];
 
@@ -62,45 +102,120 @@ pub fn execute(args: &ArgMatches) -> anyhow::Result<()> {
     //----------------------------
     println!("==> Creating archive {}", tar_name);
 
-    // Collect and filter files
-    let files: Vec<String> = cbp::find_files(collect_dir, None)?
+    // Collect and filter files, sorted for a stable entry order
+    let mut files: Vec<String> = cbp::find_files(collect_dir, None)?
         .into_iter()
         .filter(|path| !cbp::is_system_file(path))
         .filter(|path| {
             !cleanup || !doc_dirs.iter().any(|prefix| path.starts_with(prefix))
         })
         .collect();
+    files.sort();
+
+    write_archive(
+        collect_dir,
+        &files,
+        Path::new(&tar_name),
+        compression,
+        metadata.as_deref(),
+    )?;
+
+    if args.get_flag("verify-reproducible") {
+        let second = tempfile::Builder::new().prefix(".cbp-tar-").tempfile_in(
+            Path::new(&tar_name)
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new(".")),
+        )?;
+        write_archive(
+            collect_dir,
+            &files,
+            second.path(),
+            compression,
+            metadata.as_deref(),
+        )?;
+
+        let digest = cbp::sha256_file(Path::new(&tar_name))?;
+        let digest_second = cbp::sha256_file(second.path())?;
+        if digest != digest_second {
+            return Err(anyhow::anyhow!(
+                "Archive is not reproducible: {} != {}",
+                digest,
+                digest_second
+            ));
+        }
+        println!("-> Reproducible, sha256: {}", digest);
+    }
+
+    println!("==> Package created: {}", tar_name);
 
-    // Create and compress archive
-    let tar_file = std::fs::File::create(&tar_name)?;
-    let gz = flate2::GzBuilder::new()
-        .filename("")
-        .comment("")
-        .mtime(1704067200) // 2024-01-01 00:00:00 UTC
-        .write(tar_file, flate2::Compression::default());
-    let mut archive = tar::Builder::new(gz);
+    Ok(())
+}
+
+/// Write `files` under `dir` into a deterministic tarball
+///
+/// Entries keep the order of `files`, and their mtime, owner and permissions
+/// are normalized so identical inputs give bit-identical archives. `metadata`
+/// goes into the gzip header comment, or a leading pax global header for zstd
+/// and xz.
+fn write_archive(
+    dir: &Path,
+    files: &[String],
+    tar_name: &Path,
+    compression: cbp::CompressionFormat,
+    metadata: Option<&str>,
+) -> anyhow::Result<()> {
+    let tar_file = std::fs::File::create(tar_name)?;
+    let mut archive = if compression == cbp::CompressionFormat::Gzip {
+        tar::Builder::new(compression.writer(tar_file, metadata)?)
+    } else {
+        let mut archive = tar::Builder::new(compression.writer(tar_file, None)?);
+        if let Some(metadata) = metadata {
+            cbp::append_tar_comment(&mut archive, metadata)?;
+        }
+        archive
+    };
 
     // Add files with relative paths
-    for path in &files {
-        let full_path = collect_dir.join(path);
+    for path in files {
+        let full_path = dir.join(path);
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("")?;
+        header.set_groupname("")?;
+
         if full_path.is_symlink() {
+            // Store the original symlink target with empty data
             let target = std::fs::read_link(&full_path)?;
-            let mut header = tar::Header::new_gnu();
-            header.set_path(path)?;
-            header.set_size(0);
             header.set_entry_type(tar::EntryType::Symlink);
-            // Store the original symlink target
+            header.set_mode(0o777);
+            header.set_size(0);
             header.set_link_name(&target)?;
-            // Use empty data for symlink
             archive.append_data(&mut header, path, std::io::empty())?;
         } else {
-            archive.append_path_with_name(&full_path, path)?;
+            let file = std::fs::File::open(&full_path)?;
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(if is_executable(&file)? { 0o755 } else { 0o644 });
+            header.set_size(file.metadata()?.len());
+            archive.append_data(&mut header, path, file)?;
         }
     }
 
     // Finish compression
-    archive.finish()?;
-    println!("==> Package created: {}", tar_name);
+    archive.into_inner()?.finish()?;
 
     Ok(())
 }
+
+#[cfg(unix)]
+fn is_executable(file: &std::fs::File) -> std::io::Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(file.metadata()?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_file: &std::fs::File) -> std::io::Result<bool> {
+    Ok(false)
+}
@@ -8,7 +8,11 @@
 
 pub mod libs;
 
+pub use crate::libs::archive::*;
+pub use crate::libs::binary::*;
 pub use crate::libs::build::*;
 pub use crate::libs::dirs::*;
 pub use crate::libs::dot;
+pub use crate::libs::package::*;
+pub use crate::libs::relocate::*;
 pub use crate::libs::utils::*;
@@ -0,0 +1,522 @@
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+/// Archive and compression formats recognized by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+    Zip,
+    /// Single gzip-compressed file
+    Gz,
+    /// Single bzip2-compressed file
+    Bz2,
+    /// Single xz-compressed file
+    Xz,
+}
+
+impl ArchiveFormat {
+    /// Whether the format holds a single compressed file rather than a tree
+    pub fn is_single_file(&self) -> bool {
+        matches!(
+            self,
+            ArchiveFormat::Gz | ArchiveFormat::Bz2 | ArchiveFormat::Xz
+        )
+    }
+}
+
+/// Compression layer wrapping a tar stream or a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    fn reader<'a>(self, file: std::fs::File) -> anyhow::Result<Box<dyn Read + 'a>> {
+        let file = BufReader::new(file);
+        Ok(match self {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+            Compression::Zstd => {
+                Box::new(zstd::stream::read::Decoder::with_buffer(file)?)
+            }
+        })
+    }
+}
+
+/// Check for the `ustar` magic of a tar header
+fn is_tar_header(block: &[u8]) -> bool {
+    block.len() >= 262 && &block[257..262] == b"ustar"
+}
+
+/// Read up to `buf.len()` bytes, stopping early only at end of stream
+fn read_up_to(reader: &mut dyn Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match reader.read(&mut buf[total..])? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+/// Detect the archive format of a file from its content
+///
+/// Returns `None` for files that should be used as-is, including Java archives,
+/// which are zip files containing `META-INF/MANIFEST.MF`.
+pub fn detect_archive_format(path: &Path) -> anyhow::Result<Option<ArchiveFormat>> {
+    let mut magic = [0u8; 512];
+    let n = read_up_to(&mut std::fs::File::open(path)?, &mut magic)?;
+    let magic = &magic[..n];
+
+    if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+        let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+        if archive.index_for_name("META-INF/MANIFEST.MF").is_some() {
+            return Ok(None);
+        }
+        return Ok(Some(ArchiveFormat::Zip));
+    }
+
+    let compression = Compression::from_magic(magic);
+    if compression == Compression::None {
+        return Ok(is_tar_header(magic).then_some(ArchiveFormat::Tar));
+    }
+
+    // Peek into the decompressed stream for a tar header
+    let mut header = [0u8; 512];
+    let mut reader = compression.reader(std::fs::File::open(path)?)?;
+    let n = read_up_to(&mut reader, &mut header)?;
+    let is_tar = is_tar_header(&header[..n]);
+
+    Ok(Some(match (compression, is_tar) {
+        (Compression::Gzip, true) => ArchiveFormat::TarGz,
+        (Compression::Bzip2, true) => ArchiveFormat::TarBz2,
+        (Compression::Xz, true) => ArchiveFormat::TarXz,
+        (Compression::Zstd, true) => ArchiveFormat::TarZst,
+        (Compression::Gzip, false) => ArchiveFormat::Gz,
+        (Compression::Bzip2, false) => ArchiveFormat::Bz2,
+        (Compression::Xz, false) => ArchiveFormat::Xz,
+        // A bare zstd file is not a supported download format
+        _ => return Ok(None),
+    }))
+}
+
+/// Extract an archive into `dest`
+///
+/// Single compressed files are written to `dest` with the name of the archive
+/// minus its extension, e.g. `tmux.gz` becomes `tmux`.
+pub fn unpack_archive(
+    path: &Path,
+    dest: &Path,
+    format: ArchiveFormat,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    let file = std::fs::File::open(path)?;
+
+    let compression = match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+            archive.extract(dest)?;
+            return Ok(());
+        }
+        ArchiveFormat::Tar => Compression::None,
+        ArchiveFormat::TarGz | ArchiveFormat::Gz => Compression::Gzip,
+        ArchiveFormat::TarBz2 | ArchiveFormat::Bz2 => Compression::Bzip2,
+        ArchiveFormat::TarXz | ArchiveFormat::Xz => Compression::Xz,
+        ArchiveFormat::TarZst => Compression::Zstd,
+    };
+    let mut reader = compression.reader(file)?;
+
+    if format.is_single_file() {
+        let name = path
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("Invalid file name {}", path.display()))?;
+        let mut out = std::fs::File::create(dest.join(name))?;
+        std::io::copy(&mut reader, &mut out)?;
+    } else {
+        let mut archive = tar::Archive::new(reader);
+        archive.set_preserve_permissions(true);
+        archive.unpack(dest)?;
+    }
+
+    Ok(())
+}
+
+/// Compression of archives written by cbp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionFormat {
+    #[default]
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl std::str::FromStr for CompressionFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "gzip" | "gz" => Ok(CompressionFormat::Gzip),
+            "zstd" | "zst" => Ok(CompressionFormat::Zstd),
+            "xz" => Ok(CompressionFormat::Xz),
+            _ => Err(anyhow::anyhow!("Unknown compression: {}", s)),
+        }
+    }
+}
+
+impl CompressionFormat {
+    /// All formats, the default first
+    pub const ALL: [Self; 3] = [
+        CompressionFormat::Gzip,
+        CompressionFormat::Zstd,
+        CompressionFormat::Xz,
+    ];
+
+    /// Values accepted by `--compression`
+    pub const NAMES: [&'static str; 3] = ["gzip", "zstd", "xz"];
+
+    /// File extension of a tarball with this compression, e.g. `tar.zst`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompressionFormat::Gzip => "tar.gz",
+            CompressionFormat::Zstd => "tar.zst",
+            CompressionFormat::Xz => "tar.xz",
+        }
+    }
+
+    /// Detect the compression of a tarball from its magic bytes
+    ///
+    /// Returns `None` for anything other than gzip, zstd or xz.
+    pub fn detect(path: &Path) -> anyhow::Result<Option<Self>> {
+        let mut magic = [0u8; 6];
+        let n = read_up_to(&mut std::fs::File::open(path)?, &mut magic)?;
+        Ok(match Compression::from_magic(&magic[..n]) {
+            Compression::Gzip => Some(CompressionFormat::Gzip),
+            Compression::Zstd => Some(CompressionFormat::Zstd),
+            Compression::Xz => Some(CompressionFormat::Xz),
+            _ => None,
+        })
+    }
+
+    /// Wrap `writer` in an encoder
+    ///
+    /// The gzip header has a fixed mtime, and carries `comment` if given.
+    pub fn writer<W: Write>(
+        &self,
+        writer: W,
+        comment: Option<&str>,
+    ) -> std::io::Result<CompressedWriter<W>> {
+        Ok(match self {
+            CompressionFormat::Gzip => {
+                let mut builder =
+                    flate2::GzBuilder::new().filename("").mtime(1704067200); // 2024-01-01 00:00:00 UTC
+                if let Some(comment) = comment {
+                    builder = builder.comment(comment.as_bytes());
+                }
+                CompressedWriter::Gzip(
+                    builder.write(writer, flate2::Compression::default()),
+                )
+            }
+            CompressionFormat::Zstd => {
+                CompressedWriter::Zstd(zstd::stream::write::Encoder::new(writer, 19)?)
+            }
+            CompressionFormat::Xz => {
+                CompressedWriter::Xz(xz2::write::XzEncoder::new(writer, 6))
+            }
+        })
+    }
+}
+
+/// Encoder returned by [`CompressionFormat::writer`]
+pub enum CompressedWriter<W: Write> {
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Xz(xz2::write::XzEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Flush the compressed stream and return the inner writer
+    pub fn finish(self) -> std::io::Result<W> {
+        match self {
+            CompressedWriter::Gzip(e) => e.finish(),
+            CompressedWriter::Zstd(e) => e.finish(),
+            CompressedWriter::Xz(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::Gzip(e) => e.write(buf),
+            CompressedWriter::Zstd(e) => e.write(buf),
+            CompressedWriter::Xz(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::Gzip(e) => e.flush(),
+            CompressedWriter::Zstd(e) => e.flush(),
+            CompressedWriter::Xz(e) => e.flush(),
+        }
+    }
+}
+
+/// Open a tarball, detecting its compression by magic bytes
+///
+/// Plain, gzip, bzip2, xz and zstd tarballs are accepted whatever their names.
+pub fn open_archive(path: &Path) -> anyhow::Result<tar::Archive<Box<dyn Read>>> {
+    let mut magic = [0u8; 6];
+    let n = read_up_to(&mut std::fs::File::open(path)?, &mut magic)?;
+    let reader =
+        Compression::from_magic(&magic[..n]).reader(std::fs::File::open(path)?)?;
+    Ok(tar::Archive::new(reader))
+}
+
+/// Append a pax global header holding `comment`
+///
+/// Used to carry metadata in tarballs whose compression has no comment field.
+pub fn append_tar_comment<W: Write>(
+    builder: &mut tar::Builder<W>,
+    comment: &str,
+) -> anyhow::Result<()> {
+    // A pax record is "<length> <key>=<value>\n", the length counting itself
+    let body = format!(" comment={}\n", comment);
+    let mut len = body.len() + 1;
+    while len.to_string().len() + body.len() != len {
+        len += 1;
+    }
+    let record = format!("{}{}", len, body);
+
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::XGlobalHeader);
+    header.set_mtime(1704067200);
+    header.set_mode(0o644);
+    header.set_size(record.len() as u64);
+    builder.append_data(&mut header, "pax_global_header", record.as_bytes())?;
+    Ok(())
+}
+
+/// Read the package metadata embedded by `cbp tar --metadata`
+///
+/// Returns `None` for archives without metadata, or with a comment that is not
+/// package metadata, such as a snapshot.
+pub fn read_package_metadata(
+    path: &Path,
+) -> anyhow::Result<Option<crate::PackageMetadata>> {
+    let comment = crate::read_comment(path)?;
+    Ok(serde_json::from_str(&comment).ok())
+}
+
+/// Read the `comment` of a pax global header written by [`append_tar_comment`]
+pub fn read_tar_comment(path: &Path) -> anyhow::Result<Option<String>> {
+    let mut archive = open_archive(path)?;
+    let Some(entry) = archive.entries()?.next() else {
+        return Ok(None);
+    };
+    let mut entry = entry?;
+    if entry.header().entry_type() != tar::EntryType::XGlobalHeader {
+        return Ok(None);
+    }
+    if let Some(extensions) = entry.pax_extensions()? {
+        for ext in extensions {
+            let ext = ext?;
+            if ext.key()? == "comment" {
+                return Ok(Some(ext.value()?.to_string()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn tar_bytes() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let content = b"hello\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "pkg-1.0/bin/hello", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+        match compression {
+            Compression::None => data.to_vec(),
+            Compression::Gzip => {
+                let mut e = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::default(),
+                );
+                e.write_all(data).unwrap();
+                e.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut e = bzip2::write::BzEncoder::new(
+                    Vec::new(),
+                    bzip2::Compression::default(),
+                );
+                e.write_all(data).unwrap();
+                e.finish().unwrap()
+            }
+            Compression::Xz => {
+                let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+                e.write_all(data).unwrap();
+                e.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_tar_formats() -> anyhow::Result<()> {
+        let cases = [
+            (Compression::None, ArchiveFormat::Tar),
+            (Compression::Gzip, ArchiveFormat::TarGz),
+            (Compression::Bzip2, ArchiveFormat::TarBz2),
+            (Compression::Xz, ArchiveFormat::TarXz),
+            (Compression::Zstd, ArchiveFormat::TarZst),
+        ];
+        for (compression, expected) in cases {
+            let temp_dir = tempfile::tempdir()?;
+            // The name carries no hint, detection relies on content only
+            let path = temp_dir.path().join("download.tmp");
+            std::fs::write(&path, compress(&tar_bytes(), compression))?;
+
+            assert_eq!(detect_archive_format(&path)?, Some(expected));
+
+            let dest = temp_dir.path().join("out");
+            unpack_archive(&path, &dest, expected)?;
+            let hello = dest.join("pkg-1.0/bin/hello");
+            assert_eq!(std::fs::read_to_string(&hello)?, "hello\n");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(&hello)?.permissions().mode();
+                assert_eq!(mode & 0o777, 0o755);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_file_formats() -> anyhow::Result<()> {
+        let cases = [
+            (Compression::Gzip, ArchiveFormat::Gz, "tmux.gz"),
+            (Compression::Bzip2, ArchiveFormat::Bz2, "tmux.bz2"),
+            (Compression::Xz, ArchiveFormat::Xz, "tmux.xz"),
+        ];
+        for (compression, expected, name) in cases {
+            let temp_dir = tempfile::tempdir()?;
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, compress(b"\x7fELF binary", compression))?;
+
+            assert_eq!(detect_archive_format(&path)?, Some(expected));
+
+            unpack_archive(&path, temp_dir.path(), expected)?;
+            assert_eq!(
+                std::fs::read(temp_dir.path().join("tmux"))?,
+                b"\x7fELF binary"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip_format() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+
+        let write_zip = |path: &Path, names: &[&str]| -> anyhow::Result<()> {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+            for name in names {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default())?;
+                zip.write_all(b"content")?;
+            }
+            zip.finish()?;
+            Ok(())
+        };
+
+        let path = temp_dir.path().join("download.tmp");
+        write_zip(&path, &["font/Charter.ttf"])?;
+        assert_eq!(detect_archive_format(&path)?, Some(ArchiveFormat::Zip));
+        unpack_archive(&path, temp_dir.path(), ArchiveFormat::Zip)?;
+        assert!(temp_dir.path().join("font/Charter.ttf").is_file());
+
+        // Java archives are kept as-is
+        let jar = temp_dir.path().join("picard.jar");
+        write_zip(&jar, &["META-INF/MANIFEST.MF", "picard/Main.class"])?;
+        assert_eq!(detect_archive_format(&jar)?, None);
+
+        // Plain files are not archives
+        let script = temp_dir.path().join("script.sh");
+        std::fs::write(&script, "#!/bin/sh\n")?;
+        assert_eq!(detect_archive_format(&script)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression_formats() -> anyhow::Result<()> {
+        for format in CompressionFormat::ALL {
+            let temp_dir = tempfile::tempdir()?;
+            let path = temp_dir.path().join(format!("demo.{}", format.extension()));
+
+            let writer = format.writer(std::fs::File::create(&path)?, None)?;
+            let mut builder = tar::Builder::new(writer);
+            append_tar_comment(&mut builder, r#"{"sources":["~/.vimrc"]}"#)?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(6);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, "hello.txt", &b"hello\n"[..])?;
+            builder.into_inner()?.finish()?;
+
+            assert_eq!(CompressionFormat::detect(&path)?, Some(format));
+            assert_eq!(
+                read_tar_comment(&path)?.as_deref(),
+                Some(r#"{"sources":["~/.vimrc"]}"#)
+            );
+
+            let mut archive = open_archive(&path)?;
+            let paths: Vec<_> = archive
+                .entries()?
+                .filter_map(|e| e.ok())
+                .filter(|e| e.header().entry_type() != tar::EntryType::XGlobalHeader)
+                .map(|e| e.path().unwrap().to_string_lossy().into_owned())
+                .collect();
+            assert_eq!(paths, vec!["hello.txt"]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,622 @@
+use goblin::mach::{Mach, SingleArch};
+use goblin::Object;
+
+/// Executable formats recognized by [`inspect_binary`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    Pe,
+}
+
+impl std::fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            BinaryFormat::Elf => "ELF",
+            BinaryFormat::MachO => "Mach-O",
+            BinaryFormat::Pe => "PE",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// What a binary requires from the system it runs on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryInfo {
+    pub format: BinaryFormat,
+    /// Architectures, several for universal Mach-O binaries
+    pub archs: Vec<String>,
+    /// Shared library rather than executable
+    pub is_library: bool,
+    /// Shared libraries loaded at runtime, as recorded in the binary
+    pub needed: Vec<String>,
+    /// Library search paths, ELF `RPATH`/`RUNPATH` or Mach-O `LC_RPATH`
+    pub rpaths: Vec<String>,
+    /// Highest `GLIBC_*` symbol version required by an ELF binary
+    pub glibc: Option<String>,
+}
+
+/// Parse an ELF, Mach-O or PE binary
+///
+/// Returns `None` for anything else, including static libraries and object files.
+pub fn inspect_binary(bytes: &[u8]) -> Option<BinaryInfo> {
+    match Object::parse(bytes).ok()? {
+        Object::Elf(elf) => {
+            let glibc = elf
+                .verneed
+                .iter()
+                .flat_map(|section| section.iter())
+                .flat_map(|need| need.iter().collect::<Vec<_>>())
+                .filter_map(|aux| elf.dynstrtab.get_at(aux.vna_name))
+                .filter_map(|name| name.strip_prefix("GLIBC_"))
+                .filter(|v| v.starts_with(|c: char| c.is_ascii_digit()))
+                .max_by(|a, b| crate::compare_versions(a, b))
+                .map(str::to_string);
+
+            let arch = match elf.header.e_machine {
+                goblin::elf::header::EM_X86_64 => "x86_64".to_string(),
+                goblin::elf::header::EM_AARCH64 => "aarch64".to_string(),
+                goblin::elf::header::EM_386 => "i386".to_string(),
+                machine => format!("machine {}", machine),
+            };
+
+            Some(BinaryInfo {
+                format: BinaryFormat::Elf,
+                archs: vec![arch],
+                is_library: elf.is_lib,
+                needed: elf.libraries.iter().map(|s| s.to_string()).collect(),
+                rpaths: elf
+                    .rpaths
+                    .iter()
+                    .chain(elf.runpaths.iter())
+                    .flat_map(|s| s.split(':'))
+                    .map(str::to_string)
+                    .collect(),
+                glibc,
+            })
+        }
+        Object::Mach(Mach::Binary(macho)) => Some(macho_info(&[macho])),
+        Object::Mach(Mach::Fat(multi)) => {
+            let slices: Vec<_> = multi
+                .into_iter()
+                .filter_map(|arch| match arch {
+                    Ok(SingleArch::MachO(macho)) => Some(macho),
+                    _ => None,
+                })
+                .collect();
+            (!slices.is_empty()).then(|| macho_info(&slices))
+        }
+        Object::PE(pe) => {
+            let arch = match pe.header.coff_header.machine {
+                goblin::pe::header::COFF_MACHINE_X86_64 => "x86_64".to_string(),
+                goblin::pe::header::COFF_MACHINE_ARM64 => "arm64".to_string(),
+                goblin::pe::header::COFF_MACHINE_X86 => "i386".to_string(),
+                machine => format!("machine {:#x}", machine),
+            };
+
+            Some(BinaryInfo {
+                format: BinaryFormat::Pe,
+                archs: vec![arch],
+                is_library: pe.is_lib,
+                needed: pe.libraries.iter().map(|s| s.to_string()).collect(),
+                rpaths: vec![],
+                glibc: None,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Merge the slices of a Mach-O binary
+fn macho_info(slices: &[goblin::mach::MachO]) -> BinaryInfo {
+    let mut info = BinaryInfo {
+        format: BinaryFormat::MachO,
+        archs: vec![],
+        is_library: false,
+        needed: vec![],
+        rpaths: vec![],
+        glibc: None,
+    };
+
+    for macho in slices {
+        let arch = match macho.header.cputype() {
+            goblin::mach::cputype::CPU_TYPE_ARM64 => "arm64".to_string(),
+            goblin::mach::cputype::CPU_TYPE_X86_64 => "x86_64".to_string(),
+            cputype => format!("cputype {}", cputype),
+        };
+        info.archs.push(arch);
+        info.is_library |= macho.header.filetype == goblin::mach::header::MH_DYLIB;
+
+        // goblin lists a dylib's own install name as "self"
+        for lib in macho.libs.iter().filter(|lib| **lib != "self") {
+            if !info.needed.iter().any(|l| l == lib) {
+                info.needed.push(lib.to_string());
+            }
+        }
+        for rpath in &macho.rpaths {
+            if !info.rpaths.iter().any(|r| r == rpath) {
+                info.rpaths.push(rpath.to_string());
+            }
+        }
+    }
+
+    info
+}
+
+/// Check if a file is a Windows PE executable (not a DLL)
+pub fn is_windows_executable(path: &std::path::Path) -> std::io::Result<bool> {
+    let bytes = std::fs::read(path)?;
+    Ok(matches!(
+        inspect_binary(&bytes),
+        Some(BinaryInfo {
+            format: BinaryFormat::Pe,
+            is_library: false,
+            ..
+        })
+    ))
+}
+
+/// Format and architecture expected for binaries of a package type
+///
+/// Matches `TARGET_ARCH` of `scripts/common.sh`.
+pub fn expected_target(os_type: &str) -> Option<(BinaryFormat, &'static str)> {
+    match os_type {
+        "linux" => Some((BinaryFormat::Elf, "x86_64")),
+        "macos" => Some((BinaryFormat::MachO, "arm64")),
+        "windows" => Some((BinaryFormat::Pe, "x86_64")),
+        _ => None,
+    }
+}
+
+/// Libraries every system of the package type provides
+pub fn is_system_library(os_type: &str, lib: &str) -> bool {
+    match os_type {
+        "linux" => {
+            let name = lib.rsplit('/').next().unwrap_or(lib);
+            [
+                "libc.so.6",
+                "libm.so.6",
+                "libdl.so.2",
+                "librt.so.1",
+                "libpthread.so.0",
+                "libutil.so.1",
+                "libresolv.so.2",
+                "libgcc_s.so.1",
+                "libstdc++.so.6",
+                "ld-linux-x86-64.so.2",
+            ]
+            .contains(&name)
+        }
+        "macos" => lib.starts_with("/usr/lib/") || lib.starts_with("/System/Library/"),
+        "windows" => {
+            let name = lib.to_ascii_lowercase();
+            name.starts_with("api-ms-win-")
+                || [
+                    "advapi32.dll",
+                    "bcrypt.dll",
+                    "comctl32.dll",
+                    "comdlg32.dll",
+                    "crypt32.dll",
+                    "dbghelp.dll",
+                    "gdi32.dll",
+                    "imm32.dll",
+                    "iphlpapi.dll",
+                    "kernel32.dll",
+                    "msvcrt.dll",
+                    "mswsock.dll",
+                    "ntdll.dll",
+                    "ole32.dll",
+                    "oleaut32.dll",
+                    "psapi.dll",
+                    "rpcrt4.dll",
+                    "secur32.dll",
+                    "setupapi.dll",
+                    "shell32.dll",
+                    "shlwapi.dll",
+                    "ucrtbase.dll",
+                    "user32.dll",
+                    "userenv.dll",
+                    "version.dll",
+                    "winmm.dll",
+                    "ws2_32.dll",
+                    "wsock32.dll",
+                ]
+                .contains(&name.as_str())
+        }
+        _ => false,
+    }
+}
+
+/// Check a binary against the rules for a package type
+///
+/// * `os_type` - Package type: "linux", "macos" or "windows"
+/// * `glibc` - Highest glibc version allowed for linux binaries, e.g. "2.17"
+/// * `package_libs` - File names of the libraries shipped in the same package
+///
+/// Returns one message per violation.
+pub fn lint_binary(
+    info: &BinaryInfo,
+    os_type: &str,
+    glibc: &str,
+    package_libs: &[String],
+) -> Vec<String> {
+    let mut violations = vec![];
+
+    if let Some((format, arch)) = expected_target(os_type) {
+        if info.format != format {
+            violations.push(format!("{} binary in a {} package", info.format, os_type));
+            return violations;
+        }
+        if !info.archs.iter().any(|a| a == arch) {
+            violations.push(format!(
+                "built for {}, expected {}",
+                info.archs.join(", "),
+                arch
+            ));
+        }
+    }
+
+    if let Some(required) = &info.glibc {
+        if crate::compare_versions(required, glibc) == std::cmp::Ordering::Greater {
+            violations
+                .push(format!("requires GLIBC_{}, newer than {}", required, glibc));
+        }
+    }
+
+    for lib in &info.needed {
+        let name = lib.rsplit(['/', '\\']).next().unwrap_or(lib);
+        let shipped = package_libs.iter().any(|l| l.eq_ignore_ascii_case(name));
+        let relative = lib.starts_with('@') || !lib.contains('/');
+        if is_system_library(os_type, lib) || (shipped && relative) {
+            continue;
+        }
+        violations.push(format!("needs {}, not in the system or the package", lib));
+    }
+
+    for rpath in &info.rpaths {
+        if !(rpath.starts_with("$ORIGIN") || rpath.starts_with('@')) {
+            violations.push(format!("absolute rpath {}", rpath));
+        }
+    }
+
+    violations
+}
+
+/// Absolute paths of build machines embedded in a binary
+///
+/// Home directories and package manager prefixes, e.g. `/home/runner/work/`
+/// or `/opt/homebrew/`, tie a binary to the machine it was built on.
+pub fn stray_paths(bytes: &[u8]) -> Vec<String> {
+    let re = regex::bytes::Regex::new(
+        r"(/home/[\w.-]+/|/Users/[\w.-]+/|/opt/homebrew/|/usr/local/Cellar/|/nix/store/)[\w./+-]*",
+    )
+    .unwrap();
+
+    let mut paths: Vec<String> = vec![];
+    for m in re.find_iter(bytes) {
+        let path = String::from_utf8_lossy(m.as_bytes()).to_string();
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Find a program in `PATH`
+fn find_program(name: &str) -> Option<std::path::PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file() || path.with_extension("exe").is_file())
+}
+
+/// Command prefix of a tool, from an env var or the first program found
+///
+/// `zig` is tried last, as in `zig objcopy`.
+fn find_tool(env: &str, programs: &[&str], zig: Option<&str>) -> Option<Vec<String>> {
+    if let Ok(tool) = std::env::var(env) {
+        return Some(tool.split_whitespace().map(str::to_string).collect());
+    }
+    if let Some(program) = programs.iter().find(|p| find_program(p).is_some()) {
+        return Some(vec![program.to_string()]);
+    }
+    let zig_cmd = zig?;
+    find_program("zig").map(|_| vec!["zig".to_string(), zig_cmd.to_string()])
+}
+
+fn run_tool(tool: &[String], args: &[&std::ffi::OsStr]) -> anyhow::Result<()> {
+    let output = std::process::Command::new(&tool[0])
+        .args(&tool[1..])
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", tool.join(" "), e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{} failed:\n{}",
+            tool.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Strip symbols and debug info from a single ELF or Mach-O binary
+///
+/// With `debug_file`, debug info is saved there first: ELF binaries get a
+/// `.debug` file linked back via `.gnu_debuglink`, Mach-O binaries a `.dSYM`
+/// bundle from `dsymutil` if available. Returns whether debug info was saved.
+///
+/// ELF binaries use `$OBJCOPY`, `llvm-objcopy`, `objcopy` or `zig objcopy`;
+/// Mach-O binaries use `$STRIP`, `llvm-strip` or `strip`.
+pub fn strip_binary(
+    path: &std::path::Path,
+    info: &BinaryInfo,
+    debug_file: Option<&std::path::Path>,
+) -> anyhow::Result<bool> {
+    let path_os = path.as_os_str();
+    match info.format {
+        BinaryFormat::Elf => {
+            let objcopy =
+                find_tool("OBJCOPY", &["llvm-objcopy", "objcopy"], Some("objcopy"))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No objcopy found to strip {}", path.display())
+                    })?;
+
+            if let Some(debug_file) = debug_file {
+                run_tool(
+                    &objcopy,
+                    &[
+                        "--only-keep-debug".as_ref(),
+                        path_os,
+                        debug_file.as_os_str(),
+                    ],
+                )?;
+            }
+
+            let mode = if info.is_library {
+                "--strip-unneeded"
+            } else {
+                "--strip-all"
+            };
+            run_tool(&objcopy, &[mode.as_ref(), path_os, path_os])?;
+
+            if let Some(debug_file) = debug_file {
+                let link = format!("--add-gnu-debuglink={}", debug_file.display());
+                run_tool(&objcopy, &[link.as_ref(), path_os, path_os])?;
+            }
+            Ok(debug_file.is_some())
+        }
+        BinaryFormat::MachO => {
+            let mut saved = false;
+            if let Some(debug_file) = debug_file {
+                match find_tool("DSYMUTIL", &["dsymutil", "llvm-dsymutil"], None) {
+                    Some(dsymutil) => {
+                        run_tool(
+                            &dsymutil,
+                            &[path_os, "-o".as_ref(), debug_file.as_os_str()],
+                        )?;
+                        saved = true;
+                    }
+                    None => tracing::warn!(
+                        "No dsymutil found, debug info of {} is not saved",
+                        path.display()
+                    ),
+                }
+            }
+
+            let strip =
+                find_tool("STRIP", &["llvm-strip", "strip"], None).ok_or_else(|| {
+                    anyhow::anyhow!("No strip found to strip {}", path.display())
+                })?;
+            // Keep global symbols, which dylibs and plugins need
+            let temp = path.with_extension("cbp-strip");
+            run_tool(
+                &strip,
+                &[
+                    "-S".as_ref(),
+                    "-x".as_ref(),
+                    "-o".as_ref(),
+                    temp.as_os_str(),
+                    path_os,
+                ],
+            )?;
+            std::fs::rename(&temp, path)?;
+            Ok(saved)
+        }
+        BinaryFormat::Pe => Ok(false),
+    }
+}
+
+/// Strip all ELF and Mach-O binaries under `dir`
+///
+/// With `debug_dir`, debug info of each binary is saved at the same relative
+/// path there, with a `.debug` or `.dSYM` suffix.
+///
+/// # Returns
+///
+/// Relative paths of the stripped binaries with their sizes before and after
+pub fn strip_binaries(
+    dir: &std::path::Path,
+    debug_dir: Option<&std::path::Path>,
+) -> anyhow::Result<Vec<(String, u64, u64)>> {
+    let mut stripped = vec![];
+    for rel in crate::find_files(dir, None)? {
+        let path = dir.join(&rel);
+        if path.is_symlink() {
+            continue;
+        }
+        let bytes = std::fs::read(&path)?;
+        let Some(info) = inspect_binary(&bytes) else {
+            continue;
+        };
+        if info.format == BinaryFormat::Pe {
+            continue;
+        }
+
+        let debug_file = match debug_dir {
+            Some(debug_dir) => {
+                let suffix = match info.format {
+                    BinaryFormat::MachO => "dSYM",
+                    _ => "debug",
+                };
+                let debug_file = debug_dir.join(format!("{}.{}", rel, suffix));
+                if let Some(parent) = debug_file.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                Some(debug_file)
+            }
+            None => None,
+        };
+
+        strip_binary(&path, &info, debug_file.as_deref())
+            .map_err(|e| anyhow::anyhow!("Failed to strip {}: {}", rel, e))?;
+        stripped.push((rel, bytes.len() as u64, std::fs::metadata(&path)?.len()));
+    }
+    Ok(stripped)
+}
+
+/// Name of the debug archive next to a package archive
+///
+/// `jq.linux.tar.gz` becomes `jq.linux.debug.tar.gz`.
+pub fn debug_archive_name(archive: &str) -> String {
+    for format in crate::CompressionFormat::ALL {
+        let ext = format!(".{}", format.extension());
+        if let Some(stem) = archive.strip_suffix(&ext) {
+            return format!("{}.debug{}", stem, ext);
+        }
+    }
+    format!("{}.debug.tar.gz", archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_macho() -> anyhow::Result<()> {
+        let bytes = std::fs::read("tests/jq-macos-arm64")?;
+        let info = inspect_binary(&bytes).unwrap();
+
+        assert_eq!(info.format, BinaryFormat::MachO);
+        assert_eq!(info.archs, vec!["arm64"]);
+        assert!(!info.is_library);
+        assert!(info.needed.iter().all(|lib| lib.starts_with("/usr/lib/")));
+        assert!(lint_binary(&info, "macos", "2.17", &[]).is_empty());
+
+        let violations = lint_binary(&info, "linux", "2.17", &[]);
+        assert_eq!(violations, vec!["Mach-O binary in a linux package"]);
+
+        assert_eq!(inspect_binary(b"#!/bin/sh\necho hello\n"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let info = BinaryInfo {
+            format: BinaryFormat::Elf,
+            archs: vec!["x86_64".to_string()],
+            is_library: false,
+            needed: vec![
+                "libc.so.6".to_string(),
+                "libz.so.1".to_string(),
+                "libhts.so.3".to_string(),
+            ],
+            rpaths: vec!["$ORIGIN/../lib".to_string(), "/usr/local/lib".to_string()],
+            glibc: Some("2.34".to_string()),
+        };
+
+        let violations =
+            lint_binary(&info, "linux", "2.17", &["libhts.so.3".to_string()]);
+        assert_eq!(
+            violations,
+            vec![
+                "requires GLIBC_2.34, newer than 2.17",
+                "needs libz.so.1, not in the system or the package",
+                "absolute rpath /usr/local/lib",
+            ]
+        );
+
+        assert!(lint_binary(
+            &info,
+            "linux",
+            "2.34",
+            &["libz.so.1".to_string(), "libhts.so.3".to_string()]
+        )
+        .iter()
+        .all(|v| v.starts_with("absolute rpath")));
+    }
+
+    #[test]
+    fn test_stray_paths() {
+        let bytes = b"\0/home/runner/work/src/main.c\0/tmp/x\0/opt/homebrew/lib\0";
+        assert_eq!(
+            stray_paths(bytes),
+            vec!["/home/runner/work/src/main.c", "/opt/homebrew/lib"]
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_inspect_elf() -> anyhow::Result<()> {
+        let bytes = std::fs::read(std::env::current_exe()?)?;
+        let info = inspect_binary(&bytes).unwrap();
+
+        assert_eq!(info.format, BinaryFormat::Elf);
+        assert_eq!(info.archs, vec!["x86_64"]);
+        assert!(info.needed.iter().any(|lib| lib == "libc.so.6"));
+        assert!(info.glibc.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_archive_name() {
+        assert_eq!(
+            debug_archive_name("jq.linux.tar.gz"),
+            "jq.linux.debug.tar.gz"
+        );
+        assert_eq!(
+            debug_archive_name("binaries/jq.macos.tar.zst"),
+            "binaries/jq.macos.debug.tar.zst"
+        );
+        assert_eq!(debug_archive_name("jq.tar"), "jq.tar.debug.tar.gz");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_strip_binaries() -> anyhow::Result<()> {
+        if find_program("cc").is_none() || find_program("objcopy").is_none() {
+            return Ok(());
+        }
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path().join("collect");
+        std::fs::create_dir_all(dir.join("bin"))?;
+        let src = temp_dir.path().join("hello.c");
+        std::fs::write(&src, "int main(void) { return 0; }\n")?;
+        let status = std::process::Command::new("cc")
+            .args(["-g", "-o"])
+            .arg(dir.join("bin/hello"))
+            .arg(&src)
+            .status()?;
+        assert!(status.success());
+        std::fs::write(dir.join("bin/hello.sh"), "#!/bin/sh\n")?;
+
+        let debug_dir = temp_dir.path().join("debug");
+        let stripped = strip_binaries(&dir, Some(&debug_dir))?;
+        assert_eq!(stripped.len(), 1);
+        let (rel, before, after) = &stripped[0];
+        assert_eq!(rel, "bin/hello");
+        assert!(after < before);
+
+        let bytes = std::fs::read(dir.join("bin/hello"))?;
+        let elf = goblin::elf::Elf::parse(&bytes)?;
+        let names: Vec<_> = elf
+            .section_headers
+            .iter()
+            .filter_map(|sh| elf.shdr_strtab.get_at(sh.sh_name))
+            .collect();
+        assert!(names.contains(&".gnu_debuglink"));
+        assert!(!names.contains(&".debug_info"));
+        assert!(debug_dir.join("bin/hello.debug").is_file());
+        Ok(())
+    }
+}
@@ -4,12 +4,12 @@ use tracing::{info, warn};
 /// Read and validate package JSON configuration
 ///
 /// # Returns
-/// * JSON object containing package configuration
-/// * Error if package file not found or validation fails
+/// * Package configuration, with `{{version}}` in URLs and binary patterns resolved
+/// * Error if package file not found, a field is invalid or the name does not match
 pub fn read_package_json(
     base_dir: &std::path::Path,
     pkg: &str,
-) -> anyhow::Result<serde_json::Value> {
+) -> anyhow::Result<crate::Package> {
     let json_path = base_dir.join("packages").join(format!("{}.json", pkg));
     if !json_path.exists() {
         return Err(anyhow::anyhow!(
@@ -19,53 +19,127 @@ pub fn read_package_json(
     }
 
     let json_content = std::fs::read_to_string(&json_path)?;
-    let json: serde_json::Value = serde_json::from_str(&json_content)?;
+    let mut package = crate::Package::from_json(&json_content)
+        .map_err(|e| anyhow::anyhow!("Invalid {}: {}", json_path.display(), e))?;
 
     // Validate package name
-    let name = json["name"]
-        .as_str()
-        .ok_or_else(|| anyhow::anyhow!("Field 'name' not found"))?;
-    if name != pkg {
+    if package.name != pkg {
         return Err(anyhow::anyhow!(
             "Package name in JSON ({}) does not match requested package ({})",
-            name,
+            package.name,
             pkg
         ));
     }
 
-    Ok(json)
+    package.render_templates();
+    Ok(package)
 }
 
-/// Download a file from URL to the specified path
-pub fn download_file(
-    url: &str,
+/// Download a file to the specified path, trying mirror URLs in order
+///
+/// Each URL is rewritten by the mirror rules of the cbp `home` first, see
+/// [`crate::rewrite_url`].
+/// If `sha256` is given, the file is verified and a mismatch counts as a failed
+/// mirror. The error lists the failure of every mirror.
+pub fn download_file<S: AsRef<str>>(
+    urls: &[S],
     file_path: &std::path::Path,
     agent: &ureq::Agent,
+    sha256: Option<&str>,
+    home: &std::path::Path,
 ) -> anyhow::Result<()> {
     if let Some(parent) = file_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
 
+    let mut errors = Vec::new();
+    for url in urls {
+        let url = crate::rewrite_url(url.as_ref(), home)?;
+        match download_one(&url, file_path, agent, sha256) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                if urls.len() > 1 {
+                    warn!("  -> Download from {} failed: {}", url, e);
+                }
+                if file_path.exists() {
+                    std::fs::remove_file(file_path)?;
+                }
+                errors.push(format!("{}: {}", url, e));
+            }
+        }
+    }
+
+    match errors.as_slice() {
+        [] => Err(anyhow::anyhow!("No download URL given")),
+        [error] => Err(anyhow::anyhow!("{}", error)),
+        _ => Err(anyhow::anyhow!(
+            "All {} mirrors failed:\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        )),
+    }
+}
+
+fn download_one(
+    url: &str,
+    file_path: &std::path::Path,
+    agent: &ureq::Agent,
+    sha256: Option<&str>,
+) -> anyhow::Result<()> {
     let mut file = std::fs::File::create(file_path)?;
     let resp = agent.get(url).call()?;
     std::io::copy(&mut resp.into_reader(), &mut file)?;
+    drop(file);
+
+    if let Some(expected) = sha256 {
+        let actual = crate::sha256_file(file_path)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow::anyhow!(
+                "Checksum mismatch: expected {}, got {}",
+                expected,
+                actual
+            ));
+        }
+        info!("  -> Checksum verified");
+    }
     Ok(())
 }
 
-/// Extract archive using custom command or default tar
-/// Or copy single source file to destination
+/// Read a package JSON file, apply `update` and write it back
+///
+/// Key order is kept, but the file is rewritten by `serde_json`'s pretty printer:
+/// two-space indentation with every array element on its own line, the layout
+/// of `packages/*.json`. Hand-formatted parts such as inline arrays are expanded.
+pub fn update_package_json<F>(
+    base_dir: &std::path::Path,
+    pkg: &str,
+    update: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut serde_json::Value) -> anyhow::Result<()>,
+{
+    let json_path = base_dir.join("packages").join(format!("{}.json", pkg));
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json_path)?)?;
+
+    update(&mut json)?;
+
+    std::fs::write(&json_path, serde_json::to_string_pretty(&json)? + "\n")?;
+    Ok(())
+}
+
+/// Extract a downloaded archive into the temporary directory
+///
+/// The format is detected from the file content. A custom `extract` command in
+/// the package configuration overrides the built-in extraction.
 pub fn extract_archive(
     temp_dir: &tempfile::TempDir,
     temp_file: &std::path::Path,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
     info!("-> Extracting archive");
 
-    // Original archive extraction logic
-    if let Some(extract_cmd) = json_obj.get("extract") {
-        let cmd_str = extract_cmd
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Extract command must be a string"))?;
+    if let Some(cmd_str) = &download.extract {
         info!("-> Using custom extract command: {}", cmd_str);
 
         let mut parts = cmd_str.split_whitespace();
@@ -73,44 +147,131 @@ pub fn extract_archive(
             .next()
             .ok_or_else(|| anyhow::anyhow!("Empty extract command"))?;
 
-        std::process::Command::new(program)
+        let status = std::process::Command::new(program)
             .args(parts)
             .arg(temp_file)
             .current_dir(temp_dir.path())
             .status()?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "Extract command '{}' failed with {}",
+                cmd_str,
+                status
+            ));
+        }
     } else {
-        std::process::Command::new(if cfg!(target_os = "macos") {
-            "gtar"
-        } else {
-            "tar"
-        })
-        .arg("xf")
-        .arg(temp_file)
-        .current_dir(temp_dir.path())
-        .status()?;
+        let format = crate::detect_archive_format(temp_file)?.ok_or_else(|| {
+            anyhow::anyhow!("Unknown archive format: {}", temp_file.display())
+        })?;
+        info!("-> Detected format: {:?}", format);
+        crate::unpack_archive(temp_file, temp_dir.path(), format)?;
+
+        // A single compressed file without `download_name` decompresses to
+        // `download`, give it the binary name instead
+        if format.is_single_file() && download.download_name.is_none() {
+            if let Some(binary_name) = download.binary.first() {
+                let target_path = temp_dir.path().join(binary_name);
+                crate::move_file_or_dir(
+                    &temp_dir.path().join("download"),
+                    &target_path,
+                )?;
+            }
+        }
     }
     info!("  -> Extraction completed");
     Ok(())
 }
 
 /// Determine if a downloaded file needs archive extraction
+///
+/// True for any recognized archive, or when a custom `extract` command is set.
 pub fn needs_extract(
-    url: &str,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
-) -> bool {
-    url.ends_with(".zip")
-        || url.ends_with(".tar.gz")
-        || url.ends_with(".tar.xz")
-        || url.ends_with(".tar.bz2")
-        || json_obj.get("extract").is_some()
+    temp_file: &std::path::Path,
+    download: &crate::Download,
+) -> anyhow::Result<bool> {
+    Ok(download.extract.is_some() || crate::detect_archive_format(temp_file)?.is_some())
+}
+
+/// Clone a git source at `rev` into `{temp_dir}/{pkg}`, including submodules
+///
+/// Git metadata is removed and the resolved commit is recorded in
+/// `{pkg}/.cbp-git`, so the source archive stays reproducible.
+///
+/// # Returns
+/// * The resolved commit
+pub fn clone_git_source(
+    temp_dir: &tempfile::TempDir,
+    pkg: &str,
+    git: &str,
+    rev: &str,
+) -> anyhow::Result<String> {
+    fn run_git(dir: &std::path::Path, args: &[&str]) -> anyhow::Result<String> {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to run git: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "git {} failed:\n{}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    let dir = temp_dir.path().join(pkg);
+    run_git(
+        temp_dir.path(),
+        &["clone", "--quiet", "--no-checkout", git, pkg],
+    )?;
+    run_git(&dir, &["checkout", "--quiet", "--detach", rev])?;
+    // Submodules may point to local paths, e.g. in mirrors
+    run_git(
+        &dir,
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "update",
+            "--quiet",
+            "--init",
+            "--recursive",
+        ],
+    )?;
+    let commit = run_git(&dir, &["rev-parse", "HEAD"])?.trim().to_string();
+
+    // `.git` is a directory in the clone and a file in submodules
+    let git_paths: Vec<std::path::PathBuf> = walkdir::WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == ".git")
+        .map(|e| e.into_path())
+        .collect();
+    for path in git_paths {
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+
+    std::fs::write(
+        dir.join(".cbp-git"),
+        format!("git {}\nrev {}\ncommit {}\n", git, rev, commit),
+    )?;
+    info!("  -> Cloned {} at {}", git, commit);
+
+    Ok(commit)
 }
 
 /// Return the path for a temporary download file
 pub fn temp_download_path(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> std::path::PathBuf {
-    if let Some(name) = json_obj.get("download_name").and_then(|v| v.as_str()) {
+    if let Some(name) = &download.download_name {
         temp_dir.path().join(name)
     } else {
         temp_dir.path().join("download.tmp")
@@ -120,33 +281,24 @@ pub fn temp_download_path(
 /// Handle file renaming based on package configuration
 pub fn handle_rename(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
-    if let Some(rename) = json_obj.get("rename") {
+    // Only the first rename rule will be processed
+    if let Some((pattern_str, target)) = download.rename.iter().next() {
         info!("  -> Processing rename rules");
-        let rename_map = rename
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Rename must be an object"))?;
-
-        // Only the first rename rule will be processed
-        if let Some((pattern_str, target)) = rename_map.iter().next() {
-            let target = target
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Rename target must be a string"))?;
-
-            // Create parent directories if they don't exist
-            if let Some(parent) = std::path::Path::new(target).parent() {
-                std::fs::create_dir_all(temp_dir.path().join(parent))?;
-            }
 
-            // Use utils::match_files to find matching files
-            let matches = crate::match_files(temp_dir.path(), pattern_str)?;
-            if let Some((source_path, _)) = matches.first() {
-                let target_path = temp_dir.path().join(target);
-                if source_path != &target_path && source_path.exists() {
-                    crate::move_file_or_dir(source_path, &target_path)?;
-                    info!("    -> Moved: {} -> {}", source_path.display(), target);
-                }
+        // Create parent directories if they don't exist
+        if let Some(parent) = std::path::Path::new(target).parent() {
+            std::fs::create_dir_all(temp_dir.path().join(parent))?;
+        }
+
+        // Use utils::match_files to find matching files
+        let matches = crate::match_files(temp_dir.path(), pattern_str)?;
+        if let Some((source_path, _)) = matches.first() {
+            let target_path = temp_dir.path().join(target);
+            if source_path != &target_path && source_path.exists() {
+                crate::move_file_or_dir(source_path, &target_path)?;
+                info!("    -> Moved: {} -> {}", source_path.display(), target);
             }
         }
     }
@@ -157,24 +309,17 @@ pub fn handle_rename(
 #[cfg(unix)]
 pub fn handle_symlink(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
-    if let Some(symlink) = json_obj.get("symlink") {
+    if !download.symlink.is_empty() {
         info!("  -> Processing symlink rules");
-        let symlink_map = symlink
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Symlink must be an object"))?;
 
         // Create bin directory for symlinks
         let bin_dir = temp_dir.path().join("bin");
         std::fs::create_dir_all(&bin_dir)?;
 
         // Process each symlink
-        for (link_name, target) in symlink_map {
-            let target = target
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Symlink target must be a string"))?;
-
+        for (link_name, target) in &download.symlink {
             // Make target file executable
             let target_path = temp_dir.path().join(target);
             if target_path.exists() {
@@ -195,7 +340,7 @@ pub fn handle_symlink(
 #[cfg(not(unix))]
 pub fn handle_symlink(
     _temp_dir: &tempfile::TempDir,
-    _json_obj: &serde_json::Map<String, serde_json::Value>,
+    _download: &crate::Download,
 ) -> anyhow::Result<()> {
     Ok(())
 }
@@ -203,24 +348,17 @@ pub fn handle_symlink(
 /// Create Windows PowerShell shim scripts for binary symlinks
 pub fn handle_shim(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
-    if let Some(symlink) = json_obj.get("symlink") {
+    if !download.symlink.is_empty() {
         info!("  -> Processing symlink rules");
-        let symlink_map = symlink
-            .as_object()
-            .ok_or_else(|| anyhow::anyhow!("Symlink must be an object"))?;
 
         // Create bin directory for symlinks
         let bin_dir = temp_dir.path().join("bin");
         std::fs::create_dir_all(&bin_dir)?;
 
         // Process each symlink
-        for (link_name, target) in symlink_map {
-            let target = target
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Symlink target must be a string"))?;
-
+        for (link_name, target) in &download.symlink {
             let script_path = bin_dir.join(format!("{}.ps1", link_name));
 
             // Create PowerShell wrapper script
@@ -234,31 +372,14 @@ pub fn handle_shim(
 /// Create wrapper scripts for binaries based on package config
 pub fn handle_wrapper(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
-    if let Some(wrapper) = json_obj.get("wrapper").and_then(|w| w.as_object()) {
+    if !download.wrapper.is_empty() {
         std::fs::create_dir_all(temp_dir.path().join("bin"))?;
 
-        for (name, content) in wrapper {
+        for (name, content) in &download.wrapper {
             let script_path = temp_dir.path().join("bin").join(name);
-            let content = match content {
-                serde_json::Value::String(s) => s.to_string(),
-                serde_json::Value::Array(lines) => {
-                    lines
-                        .iter()
-                        .filter_map(|line| line.as_str())
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                        + "\n"
-                }
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Wrapper content must be a string or array"
-                    ))
-                }
-            };
-
-            std::fs::write(&script_path, content)?;
+            std::fs::write(&script_path, &content.0)?;
 
             #[cfg(unix)]
             {
@@ -273,22 +394,78 @@ pub fn handle_wrapper(
     Ok(())
 }
 
-/// Clean files matching patterns specified in package configuration
-pub fn clean_files(
+/// Apply patches from `patches/{pkg}/` to the extracted source
+///
+/// Patches run in the single top-level directory of the source, or in the
+/// extraction root when there is more than one entry. Each patch is checked with
+/// `--dry-run` first, so a failing patch leaves the tree untouched.
+pub fn apply_patches(
     temp_dir: &tempfile::TempDir,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    temp_file: &std::path::Path,
+    base_dir: &std::path::Path,
+    pkg: &str,
+    download: &crate::Download,
 ) -> anyhow::Result<()> {
-    if let Some(clean) = json_obj.get("clean") {
-        let clean_paths = clean
-            .as_array()
-            .ok_or_else(|| anyhow::anyhow!("Clean must be an array"))?;
+    if download.patches.is_empty() {
+        return Ok(());
+    }
+    info!("  -> Applying {} patches", download.patches.len());
+
+    let entries: Vec<std::path::PathBuf> = std::fs::read_dir(temp_dir.path())?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    let entries: Vec<_> = entries.into_iter().filter(|p| p != temp_file).collect();
+    let work_dir = match entries.as_slice() {
+        [dir] if dir.is_dir() => dir.clone(),
+        _ => temp_dir.path().to_path_buf(),
+    };
 
-        info!("  -> Cleaning {} patterns", clean_paths.len());
-        for path in clean_paths {
-            let path_str = path
-                .as_str()
-                .ok_or_else(|| anyhow::anyhow!("Clean path must be a string"))?;
+    let patch_dir = base_dir.join("patches").join(pkg);
+    for patch in &download.patches {
+        let patch_file = patch_dir.join(&patch.file);
+        if !patch_file.is_file() {
+            return Err(anyhow::anyhow!(
+                "Patch file {} not found",
+                patch_file.display()
+            ));
+        }
+        let patch_file = patch_file.canonicalize()?;
+
+        for dry_run in [true, false] {
+            let mut cmd = std::process::Command::new("patch");
+            cmd.arg(format!("-p{}", patch.strip))
+                .args(["--forward", "--batch", "--input"])
+                .arg(&patch_file)
+                .current_dir(&work_dir);
+            if dry_run {
+                cmd.arg("--dry-run");
+            }
+            let output = cmd.output().map_err(|e| {
+                anyhow::anyhow!("Failed to run patch for {}: {}", patch.file, e)
+            })?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "Patch {} does not apply to {}:\n{}{}",
+                    patch.file,
+                    pkg,
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+        }
+        info!("    -> Applied: {}", patch.file);
+    }
+    Ok(())
+}
 
+/// Clean files matching patterns specified in package configuration
+pub fn clean_files(
+    temp_dir: &tempfile::TempDir,
+    download: &crate::Download,
+) -> anyhow::Result<()> {
+    if !download.clean.is_empty() {
+        info!("  -> Cleaning {} patterns", download.clean.len());
+        for path_str in &download.clean {
             // find matching files
             let matches = crate::match_files(temp_dir.path(), path_str)?;
             for (path, rel_path) in matches {
@@ -307,21 +484,11 @@ pub fn clean_files(
 /// Find binary files matching patterns in package configuration
 pub fn find_binary_files(
     temp_dir: &std::path::Path,
-    json_obj: &serde_json::Map<String, serde_json::Value>,
+    download: &crate::Download,
 ) -> anyhow::Result<Vec<String>> {
-    let patterns: Vec<String> = match &json_obj["binary"] {
-        serde_json::Value::String(pattern) => vec![pattern.to_string()],
-        serde_json::Value::Array(arr) => arr
-            .iter()
-            .filter_map(|v| v.as_str())
-            .map(|s| s.to_string())
-            .collect(),
-        _ => return Err(anyhow::anyhow!("Invalid binary format")),
-    };
-
     let mut binary_paths = Vec::new();
-    for pattern in patterns {
-        let matches = crate::libs::utils::match_files(temp_dir, &pattern)?;
+    for pattern in &download.binary {
+        let matches = crate::libs::utils::match_files(temp_dir, pattern)?;
         binary_paths.extend(matches.into_iter().map(|(_, rel_path)| rel_path));
     }
 
@@ -429,3 +596,66 @@ pub fn normalize_line_endings(path: &std::path::Path) -> anyhow::Result<()> {
     std::fs::write(path, normalized)?;
     Ok(())
 }
+
+/// Check if a file name looks like a license text, e.g. `LICENSE`,
+/// `COPYING.LIB`, `LICENSE-MIT` or vcpkg's `copyright`
+pub fn is_license_file(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    [
+        "LICENSE",
+        "LICENCE",
+        "COPYING",
+        "COPYRIGHT",
+        "NOTICE",
+        "UNLICENSE",
+    ]
+    .iter()
+    .any(|prefix| name.starts_with(prefix))
+}
+
+/// License files at the top of a source tree, or of its only subdirectory
+pub fn find_license_files(
+    dir: &std::path::Path,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            dirs.push(path);
+        } else if path.is_file() && is_license_file(&name) {
+            files.push(path);
+        }
+    }
+
+    // Archives usually extract into a single `{name}-{version}/` directory
+    if files.is_empty() && dirs.len() == 1 {
+        return find_license_files(&dirs[0]);
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Copy license files into `share/licenses/{pkg}/` under `dest_dir`
+///
+/// Returns the paths of the copies, relative to `dest_dir`.
+pub fn copy_licenses(
+    files: &[std::path::PathBuf],
+    dest_dir: &std::path::Path,
+    pkg: &str,
+) -> anyhow::Result<Vec<String>> {
+    let license_dir = format!("share/licenses/{}", pkg);
+    let mut copied = vec![];
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let rel_path = format!("{}/{}", license_dir, name);
+        if copied.contains(&rel_path) {
+            continue;
+        }
+        std::fs::create_dir_all(dest_dir.join(&license_dir))?;
+        std::fs::copy(file, dest_dir.join(&rel_path))?;
+        copied.push(rel_path);
+    }
+    Ok(copied)
+}
@@ -1,6 +1,42 @@
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::path::PathBuf;
 
+/// Install metadata of a package, stored as `records/{pkg}.json`
+///
+/// Packages installed before records existed are treated as explicit
+/// installs without dependencies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageRecord {
+    /// Name of the package
+    pub name: String,
+    /// Installed on request rather than as a dependency of another package
+    #[serde(default = "default_explicit")]
+    pub explicit: bool,
+    /// Packages this package depends on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Metadata embedded in the package archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<crate::PackageMetadata>,
+}
+
+fn default_explicit() -> bool {
+    true
+}
+
+impl PackageRecord {
+    /// Creates a record for an explicitly installed package without dependencies
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            explicit: true,
+            dependencies: Vec::new(),
+            metadata: None,
+        }
+    }
+}
+
 /// Represents CBP directory structure
 pub struct CbpDirs {
     /// Installation directory
@@ -102,37 +138,337 @@ impl CbpDirs {
         }
     }
 
-    /// Install package from a tar.gz file
+    /// Install package from a tarball
+    ///
+    /// Metadata embedded in the archive is saved in a new explicit install record.
     ///
     /// # Arguments
     ///
     /// * `pkg_name` - Name of the package
-    /// * `pkg_file` - Path to the package tar.gz file
+    /// * `pkg_file` - Path to the package tarball
     pub fn install_package(
         &self,
         pkg_name: &str,
         pkg_file: &Path,
     ) -> anyhow::Result<()> {
         println!("==> Installing {}", pkg_name);
+        self.extract_package(pkg_name, pkg_file)?;
+
+        let mut record = PackageRecord::new(pkg_name);
+        record.metadata = crate::read_package_metadata(pkg_file)?;
+        if let Some(metadata) = &record.metadata {
+            println!("    Version {}", metadata.version);
+        }
+        self.write_record(&record)?;
+        println!("    Done");
+        Ok(())
+    }
 
+    /// Extract a package tarball and record its file list, without
+    /// printing progress
+    pub fn extract_package(
+        &self,
+        pkg_name: &str,
+        pkg_file: &Path,
+    ) -> anyhow::Result<()> {
         // List files in package
         let record_file = self.records.join(format!("{}.files", pkg_name));
         let file_list = crate::list_archive_files(pkg_file)?;
 
         // Save file list
-        std::fs::write(&record_file, file_list)?;
+        std::fs::write(&record_file, &file_list)?;
 
-        // Extract files
-        let file = std::fs::File::open(pkg_file)?;
-        let gz = flate2::read::GzDecoder::new(file);
-        let mut archive = tar::Archive::new(gz);
+        // Extract files, whatever the compression
+        let mut archive = crate::open_archive(pkg_file)?;
 
         if let Err(e) = archive.unpack(&self.home) {
             std::fs::remove_file(record_file)?;
             return Err(anyhow::anyhow!("    Failed to extract {}: {}", pkg_name, e));
         }
 
-        println!("    Done");
+        // Point scripts, wrappers and config files at this home, which may
+        // have been given as a relative --dir
+        let home = dunce::canonicalize(&self.home)?;
+        let files: Vec<&str> = file_list.lines().collect();
+        crate::substitute_prefix(&home, &files)?;
+
+        Ok(())
+    }
+
+    /// Download a package from the GitHub release into the cache directory
+    ///
+    /// # Arguments
+    ///
+    /// * `pkg_name` - Name of the package
+    /// * `pkg_type` - Package type: "linux", "macos", "windows" or "font"
+    /// * `agent` - HTTP agent used for the download
+    ///
+    /// # Returns
+    ///
+    /// Path to the cached package file
+    pub fn download_package(
+        &self,
+        pkg_name: &str,
+        pkg_type: &str,
+        agent: &ureq::Agent,
+    ) -> anyhow::Result<PathBuf> {
+        let pkg_file = format!("{}.{}.tar.gz", pkg_name, pkg_type);
+        let temp_file = self.cache.join(format!("{}.incomplete", pkg_file));
+        let cache_file = self.cache.join(&pkg_file);
+
+        // Create cache directory if needed
+        std::fs::create_dir_all(&self.cache)?;
+
+        // Download from GitHub
+        let base_url = crate::github_release_url();
+        let url = format!(
+            "{}/wang-q/cbp/releases/download/Binaries/{}",
+            base_url, pkg_file
+        );
+        let url = crate::rewrite_url(&url, &self.home)?;
+        let mut file = std::fs::File::create(&temp_file)?;
+        let resp = agent.get(&url).call()?;
+        std::io::copy(&mut resp.into_reader(), &mut file)?;
+
+        // Move to final location using move_file_or_dir to handle cross-device scenarios
+        crate::move_file_or_dir(&temp_file, &cache_file)?;
+
+        Ok(cache_file)
+    }
+
+    /// Returns names of all installed packages, sorted
+    pub fn installed_packages(&self) -> anyhow::Result<Vec<String>> {
+        let files = crate::find_files(&self.records, Some("*.files"))?;
+        Ok(files
+            .iter()
+            .filter(|f| !crate::is_system_file(f))
+            .filter_map(|f| f.strip_suffix(".files").map(|s| s.to_string()))
+            .collect())
+    }
+
+    /// Returns files under the home directory not listed in any package record
+    ///
+    /// Files used by cbp itself and system generated files are ignored.
+    pub fn unmanaged_files(&self) -> anyhow::Result<Vec<String>> {
+        // Collect all known files from installed packages
+        let mut known_files = std::collections::HashSet::new();
+        for pkg in self.installed_packages()? {
+            known_files.extend(self.package_files(&pkg)?);
+        }
+
+        // Find files not in the known list
+        let all_files = crate::find_files(&self.home, None)?;
+        Ok(all_files
+            .into_iter()
+            .filter(|file| {
+                !crate::is_cbp_file(file)
+                    && !crate::is_system_file(file)
+                    && !known_files.contains(file)
+            })
+            .collect())
+    }
+
+    /// Returns the file list recorded for an installed package
+    pub fn package_files(&self, pkg_name: &str) -> anyhow::Result<Vec<String>> {
+        let record_file = self.records.join(format!("{}.files", pkg_name));
+        let content = std::fs::read_to_string(&record_file)?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    /// Read the install metadata of a package
+    ///
+    /// Returns a default explicit record if no metadata was saved
+    pub fn read_record(&self, pkg_name: &str) -> anyhow::Result<PackageRecord> {
+        let path = self.records.join(format!("{}.json", pkg_name));
+        if !path.exists() {
+            return Ok(PackageRecord::new(pkg_name));
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save the install metadata of a package
+    pub fn write_record(&self, record: &PackageRecord) -> anyhow::Result<()> {
+        let path = self.records.join(format!("{}.json", record.name));
+        std::fs::write(path, serde_json::to_string_pretty(record)? + "\n")?;
+        Ok(())
+    }
+
+    /// Returns installed packages that depend on the given package
+    pub fn dependents(&self, pkg_name: &str) -> anyhow::Result<Vec<String>> {
+        let mut result = Vec::new();
+        for other in self.installed_packages()? {
+            if self
+                .read_record(&other)?
+                .dependencies
+                .iter()
+                .any(|d| d == pkg_name)
+            {
+                result.push(other);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns packages installed as dependencies that nothing needs anymore
+    ///
+    /// A package is an orphan if it was not explicitly installed and every
+    /// installed package depending on it is in `removing` or is an orphan itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `removing` - Packages about to be removed
+    pub fn orphan_packages(&self, removing: &[String]) -> anyhow::Result<Vec<String>> {
+        let mut records = Vec::new();
+        for pkg in self.installed_packages()? {
+            records.push(self.read_record(&pkg)?);
+        }
+
+        let mut gone: Vec<String> = removing.to_vec();
+        let mut orphans = Vec::new();
+        loop {
+            let found: Vec<String> = records
+                .iter()
+                .filter(|r| !r.explicit && !gone.contains(&r.name))
+                .filter(|r| {
+                    !records.iter().any(|other| {
+                        !gone.contains(&other.name)
+                            && other.dependencies.contains(&r.name)
+                    })
+                })
+                .map(|r| r.name.clone())
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            gone.extend(found.iter().cloned());
+            orphans.extend(found);
+        }
+
+        orphans.sort();
+        Ok(orphans)
+    }
+
+    /// Remove an installed package guided by its record
+    ///
+    /// Files also listed in the records of other installed packages are kept.
+    /// Directories left empty after removal are pruned.
+    ///
+    /// # Arguments
+    ///
+    /// * `pkg_name` - Name of the package
+    /// * `batch` - Other packages removed in the same run, whose records are
+    ///   not considered when looking for shared files
+    /// * `dry_run` - Only print what would be removed
+    pub fn remove_package(
+        &self,
+        pkg_name: &str,
+        batch: &[String],
+        dry_run: bool,
+    ) -> anyhow::Result<()> {
+        let record_file = self.records.join(format!("{}.files", pkg_name));
+
+        // Files owned by other installed packages
+        let mut shared = std::collections::HashMap::new();
+        for other in self.installed_packages()? {
+            if other == pkg_name || batch.contains(&other) {
+                continue;
+            }
+            for file in self.package_files(&other)? {
+                shared.entry(file).or_insert_with(|| other.clone());
+            }
+        }
+
+        let mut dirs = std::collections::BTreeSet::new();
+        for line in self.package_files(pkg_name)? {
+            let file = self.home.join(&line);
+            if line.ends_with('/') {
+                dirs.insert(file);
+                continue;
+            }
+            if let Some(owner) = shared.get(&line) {
+                println!("    Skipped shared file: {} (also in {})", line, owner);
+                continue;
+            }
+
+            if !file.exists() && !file.is_symlink() {
+                println!("    File not found: {}", file.display());
+                continue;
+            }
+
+            if !file.is_file() && !file.is_symlink() {
+                continue;
+            }
+
+            if let Some(parent) = file.parent() {
+                dirs.insert(parent.to_path_buf());
+            }
+
+            if dry_run {
+                println!("    Would remove: {}", line);
+                continue;
+            }
+
+            std::fs::remove_file(&file)?;
+
+            // Handle resource fork files
+            let file_name = match file.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let resource_fork = file.parent().unwrap().join(format!("._{}", file_name));
+            if resource_fork.exists() {
+                std::fs::remove_file(&resource_fork)?;
+            }
+        }
+
+        if dry_run {
+            return Ok(());
+        }
+
+        self.prune_empty_dirs(dirs)?;
+        std::fs::remove_file(&record_file)?;
+        let meta_file = self.records.join(format!("{}.json", pkg_name));
+        if meta_file.exists() {
+            std::fs::remove_file(&meta_file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove empty directories and their empty ancestors below the home directory
+    ///
+    /// The `bin/`, `cache/` and `records/` directories are never removed.
+    fn prune_empty_dirs(
+        &self,
+        dirs: std::collections::BTreeSet<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let keep = [&self.home, &self.bin, &self.cache, &self.records];
+
+        // Deepest directories first, so parents are emptied before checked
+        let mut dirs: Vec<PathBuf> = dirs.into_iter().collect();
+        dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+
+        for dir in dirs {
+            let mut current = Some(dir.as_path());
+            while let Some(dir) = current {
+                if keep.iter().any(|k| k.as_path() == dir)
+                    || !dir.starts_with(&self.home)
+                    || !dir.is_dir()
+                    || std::fs::read_dir(dir)?.next().is_some()
+                {
+                    break;
+                }
+                std::fs::remove_dir(dir)?;
+                current = dir.parent();
+            }
+        }
+
         Ok(())
     }
 }
@@ -227,7 +563,7 @@ mod tests {
         // Create temporary directory for testing
         let temp_dir = tempfile::tempdir()?;
         let base_dir = temp_dir.path();
-        std::env::set_current_dir(&base_dir)?;
+        std::env::set_current_dir(base_dir)?;
 
         // Test relative paths with different formats
         let test_cases = vec!["relative/path", "./relative/path", "relative/./path"];
@@ -278,4 +614,80 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_remove_package() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cbp_dirs = CbpDirs::from(temp_dir.path().to_path_buf())?;
+
+        // Two packages sharing a file
+        let home = &cbp_dirs.home;
+        std::fs::create_dir_all(home.join("share/doc/foo"))?;
+        std::fs::create_dir_all(home.join("include/foo"))?;
+        std::fs::write(home.join("share/doc/foo/README"), "")?;
+        std::fs::write(home.join("include/foo/foo.h"), "")?;
+        std::fs::write(home.join("include/common.h"), "")?;
+        std::fs::write(home.join("bin/foo"), "")?;
+        std::fs::write(
+            cbp_dirs.records.join("foo.files"),
+            "bin/\nbin/foo\ninclude/\ninclude/common.h\ninclude/foo/\ninclude/foo/foo.h\nshare/\nshare/doc/\nshare/doc/foo/\nshare/doc/foo/README\n",
+        )?;
+        std::fs::write(
+            cbp_dirs.records.join("bar.files"),
+            "include/\ninclude/common.h\n",
+        )?;
+
+        // Dry run leaves everything in place
+        cbp_dirs.remove_package("foo", &[], true)?;
+        assert!(home.join("bin/foo").exists());
+        assert!(cbp_dirs.records.join("foo.files").exists());
+
+        cbp_dirs.remove_package("foo", &[], false)?;
+        assert!(!cbp_dirs.records.join("foo.files").exists());
+        assert!(!home.join("bin/foo").exists());
+        assert!(!home.join("share").exists());
+        assert!(!home.join("include/foo").exists());
+        assert!(home.join("include/common.h").exists());
+        assert!(cbp_dirs.bin.exists());
+
+        // Shared files are removed with the last package
+        cbp_dirs.remove_package("bar", &[], false)?;
+        assert!(!home.join("include").exists());
+        assert!(cbp_dirs.records.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphan_packages() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cbp_dirs = CbpDirs::from(temp_dir.path().to_path_buf())?;
+
+        // app -> lib1 -> lib2, tool -> lib3, legacy without metadata
+        for (name, explicit, deps) in [
+            ("app", true, vec!["lib1"]),
+            ("lib1", false, vec!["lib2"]),
+            ("lib2", false, vec![]),
+            ("lib3", false, vec![]),
+        ] {
+            std::fs::write(cbp_dirs.records.join(format!("{}.files", name)), "")?;
+            cbp_dirs.write_record(&PackageRecord {
+                name: name.to_string(),
+                explicit,
+                dependencies: deps.into_iter().map(String::from).collect(),
+                metadata: None,
+            })?;
+        }
+        std::fs::write(cbp_dirs.records.join("legacy.files"), "")?;
+
+        assert!(cbp_dirs.read_record("legacy")?.explicit);
+        assert_eq!(cbp_dirs.dependents("lib1")?, vec!["app"]);
+        assert_eq!(cbp_dirs.orphan_packages(&[])?, vec!["lib3"]);
+        assert_eq!(
+            cbp_dirs.orphan_packages(&["app".to_string()])?,
+            vec!["lib1", "lib2", "lib3"]
+        );
+
+        Ok(())
+    }
 }
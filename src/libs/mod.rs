@@ -1,10 +1,18 @@
 //! Core library modules
 //!
+//! - archive: Archive format detection and extraction
+//! - binary: ELF, Mach-O and PE inspection and linting
 //! - dirs: Directory structure management
 //! - utils: Common utilities and functions
 //! - dot: Dotfiles management
+//! - package: Package configuration model
+//! - relocate: Build prefix placeholders in packaged files
 
+pub mod archive;
+pub mod binary;
 pub mod build;
 pub mod dirs;
 pub mod dot;
+pub mod package;
+pub mod relocate;
 pub mod utils;
@@ -0,0 +1,687 @@
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Package configuration from `packages/{pkg}.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub homepage: String,
+    pub license: String,
+    #[serde(rename = "type")]
+    pub pkg_type: PackageType,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Downloads keyed by `source`, `linux`, `macos`, `windows` or `font`
+    #[serde(default)]
+    pub downloads: BTreeMap<String, Download>,
+    #[serde(default)]
+    pub build: Build,
+    pub collect: Option<Collect>,
+    pub livecheck: Option<Livecheck>,
+    #[serde(default)]
+    pub tests: Vec<TestCase>,
+}
+
+/// Build type of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageType {
+    Prebuild,
+    Rust,
+    Make,
+    Cmake,
+    Autotools,
+    Vcpkg,
+    Font,
+    Source,
+}
+
+impl std::fmt::Display for PackageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            PackageType::Prebuild => "prebuild",
+            PackageType::Rust => "rust",
+            PackageType::Make => "make",
+            PackageType::Cmake => "cmake",
+            PackageType::Autotools => "autotools",
+            PackageType::Vcpkg => "vcpkg",
+            PackageType::Font => "font",
+            PackageType::Source => "source",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A download entry and how to turn it into package files
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Download {
+    /// Download URL followed by mirrors, tried in order; empty for git sources
+    #[serde(rename = "url", default, deserialize_with = "string_or_vec")]
+    pub urls: Vec<String>,
+    /// Git repository, cloned at `rev` instead of downloading `url`
+    pub git: Option<String>,
+    /// Tag, branch or commit of `git`
+    pub rev: Option<String>,
+    /// Expected SHA-256 of the downloaded file
+    pub sha256: Option<String>,
+    /// File name for the download, used by extractors that check extensions
+    pub download_name: Option<String>,
+    /// Custom extraction command, the downloaded file is appended
+    pub extract: Option<String>,
+    /// Files to package into `bin/`, as glob patterns
+    #[serde(default, deserialize_with = "string_or_vec")]
+    pub binary: Vec<String>,
+    /// Directory to pack as a whole
+    pub tar: Option<String>,
+    /// Only the first rule is applied
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    /// Link name in `bin/` to target path
+    #[serde(default)]
+    pub symlink: BTreeMap<String, String>,
+    /// Script name in `bin/` to content
+    #[serde(default)]
+    pub wrapper: BTreeMap<String, Script>,
+    /// Extra downloads packed into the same source archive
+    #[serde(default)]
+    pub resources: Vec<Resource>,
+    /// Patches under `patches/{pkg}/`, applied after renaming
+    #[serde(default)]
+    pub patches: Vec<Patch>,
+    /// Glob patterns of files to remove
+    #[serde(default, deserialize_with = "string_or_vec")]
+    pub clean: Vec<String>,
+    #[serde(default)]
+    pub shebang: bool,
+}
+
+/// An extra source download placed in `dest`
+///
+/// Takes the `url`, `sha256`, `download_name`, `extract`, `rename` and `clean`
+/// fields of a download, with `rename` and `clean` relative to the resource.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Resource {
+    /// Directory in the source tree
+    pub dest: String,
+    #[serde(flatten)]
+    pub download: Download,
+}
+
+/// A patch file and its strip level, given as a file name or an object
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "PatchDef")]
+pub struct Patch {
+    pub file: String,
+    pub strip: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatchDef {
+    File(String),
+    Object {
+        file: String,
+        #[serde(default = "default_strip")]
+        strip: u32,
+    },
+}
+
+fn default_strip() -> u32 {
+    1
+}
+
+impl From<PatchDef> for Patch {
+    fn from(def: PatchDef) -> Self {
+        match def {
+            PatchDef::File(file) => Patch { file, strip: 1 },
+            PatchDef::Object { file, strip } => Patch { file, strip },
+        }
+    }
+}
+
+/// Settings for `cbp build compile`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Build {
+    #[serde(default)]
+    pub configure: Vec<String>,
+    #[serde(default)]
+    pub make_args: Vec<String>,
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub no_default_features: bool,
+    #[serde(default)]
+    pub binaries: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Strip symbols from binaries before packing
+    #[serde(default)]
+    pub strip: bool,
+    /// Strip, and save debug info into `{pkg}.{type}.debug.tar.gz`
+    #[serde(default)]
+    pub split_debug: bool,
+}
+
+/// Package layout for `cbp collect --package` and `cbp build compile`
+///
+/// Each list holds glob patterns relative to the build directory; matched
+/// files, or whole directories, are placed into `bin/`, `lib/`, `include/`
+/// or `share/` under their own names.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Collect {
+    #[serde(default)]
+    pub bin: Vec<String>,
+    #[serde(default)]
+    pub lib: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub share: Vec<String>,
+    /// Glob or `re:` regex patterns of files to leave out
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+impl Collect {
+    /// Target directories with their patterns
+    pub fn layout(&self) -> [(&'static str, &[String]); 4] {
+        [
+            ("bin", &self.bin),
+            ("lib", &self.lib),
+            ("include", &self.include),
+            ("share", &self.share),
+        ]
+    }
+}
+
+/// Where `cbp build livecheck` looks for upstream versions
+///
+/// Without `url`, GitHub releases and tags of the repository found in the
+/// homepage or download URLs are used.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Livecheck {
+    pub url: Option<String>,
+    /// The first capture group is the version
+    pub regex: Option<String>,
+}
+
+/// Metadata embedded in binary package archives
+///
+/// Written by `cbp tar --metadata` and saved into `records/{pkg}.json` on install.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    /// URL or git repository the package was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Git revision of the source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// UTC build time, e.g. `2024-01-01T00:00:00Z`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built: Option<String>,
+}
+
+/// A test case run by `cbp build test`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub command: String,
+    /// Regular expression matched against stdout and stderr
+    pub pattern: String,
+    #[serde(default, deserialize_with = "string_or_vec")]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub ignore_exit_code: bool,
+}
+
+/// Script content, given as a string or an array of lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script(pub String);
+
+impl<'de> Deserialize<'de> for Script {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ScriptVisitor;
+
+        impl<'de> Visitor<'de> for ScriptVisitor {
+            type Value = Script;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string or an array of lines")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Script, E> {
+                Ok(Script(v.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Script, A::Error> {
+                let mut lines = Vec::new();
+                while let Some(line) = seq.next_element::<String>()? {
+                    lines.push(line);
+                }
+                Ok(Script(lines.join("\n") + "\n"))
+            }
+        }
+
+        deserializer.deserialize_any(ScriptVisitor)
+    }
+}
+
+/// Deserialize a single string or an array of strings into a Vec
+fn string_or_vec<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    struct StringOrVec;
+
+    impl<'de> Visitor<'de> for StringOrVec {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string or an array of strings")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<String>, E> {
+            Ok(vec![v.to_string()])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> Result<Vec<String>, A::Error> {
+            let mut items = Vec::new();
+            while let Some(item) = seq.next_element::<String>()? {
+                items.push(item);
+            }
+            Ok(items)
+        }
+    }
+
+    deserializer.deserialize_any(StringOrVec)
+}
+
+impl Package {
+    /// Parse package JSON, errors name the offending field (e.g. `tests[0].command`)
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let de = &mut serde_json::Deserializer::from_str(json);
+        let package: Package = serde_path_to_error::deserialize(de).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                anyhow::anyhow!("{}", e.into_inner())
+            } else {
+                anyhow::anyhow!("{}: {}", path, e.into_inner())
+            }
+        })?;
+
+        // Either `url`, or `git` pinned by `rev`
+        for (key, download) in &package.downloads {
+            match (&download.git, &download.rev) {
+                (Some(_), None) => {
+                    return Err(anyhow::anyhow!(
+                        "downloads.{}.rev: required with `git`",
+                        key
+                    ))
+                }
+                (None, _) if download.urls.is_empty() => {
+                    return Err(anyhow::anyhow!(
+                        "downloads.{}: missing field `url`",
+                        key
+                    ))
+                }
+                _ => {}
+            }
+            for (i, resource) in download.resources.iter().enumerate() {
+                if resource.download.urls.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "downloads.{}.resources[{}]: missing field `url`",
+                        key,
+                        i
+                    ));
+                }
+            }
+        }
+
+        Ok(package)
+    }
+
+    /// Substitute `{{version}}` in download URLs and binary patterns,
+    /// including those of resources
+    pub fn render_templates(&mut self) {
+        let version = self.version.clone();
+        for download in self.downloads.values_mut() {
+            download.render_templates(&version);
+        }
+    }
+
+    /// Metadata of a build from the download `key`
+    ///
+    /// Falls back to the `source` download for the source location, and stamps
+    /// the build time with [`crate::build_timestamp`].
+    pub fn metadata(&self, key: &str) -> PackageMetadata {
+        let download = self
+            .downloads
+            .get(key)
+            .or_else(|| self.downloads.get("source"));
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+
+        PackageMetadata {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            license: non_empty(&self.license),
+            homepage: non_empty(&self.homepage),
+            source: download.and_then(|d| d.git.clone().or_else(|| non_empty(d.url()))),
+            commit: download.and_then(|d| d.rev.clone()),
+            built: Some(crate::build_timestamp()),
+        }
+    }
+
+    /// Get a download entry by key
+    pub fn download(&self, key: &str) -> anyhow::Result<&Download> {
+        self.downloads
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("Download configuration '{}' not found", key))
+    }
+}
+
+impl Download {
+    /// The primary download URL, empty for git sources
+    pub fn url(&self) -> &str {
+        self.urls.first().map(String::as_str).unwrap_or_default()
+    }
+
+    fn render_templates(&mut self, version: &str) {
+        for url in self.urls.iter_mut() {
+            *url = url.replace("{{version}}", version);
+        }
+        for pattern in self.binary.iter_mut() {
+            *pattern = pattern.replace("{{version}}", version);
+        }
+        for resource in self.resources.iter_mut() {
+            resource.download.render_templates(version);
+        }
+    }
+
+    /// Whether the download is used as-is, without extraction or post-processing
+    pub fn is_url_only(&self) -> bool {
+        self.download_name.is_none()
+            && self.extract.is_none()
+            && self.binary.is_empty()
+            && self.tar.is_none()
+            && self.rename.is_empty()
+            && self.symlink.is_empty()
+            && self.wrapper.is_empty()
+            && self.git.is_none()
+            && self.resources.is_empty()
+            && self.patches.is_empty()
+            && self.clean.is_empty()
+    }
+}
+
+impl TestCase {
+    /// Arguments with `$(cbp prefix)` replaced by the cbp home directory
+    pub fn resolved_args(&self, home: &std::path::Path) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|s| s.replace("$(cbp prefix)", &home.to_string_lossy()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Deserializer that records the field names of the requested struct
+    struct FieldNames<'a>(&'a mut Vec<&'static str>);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(
+            self,
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.0.extend(fields);
+            Err(de::Error::custom("fields collected"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    fn fields<T: for<'de> Deserialize<'de>>() -> BTreeSet<String> {
+        let mut names = Vec::new();
+        let _ = T::deserialize(FieldNames(&mut names));
+        names.into_iter().map(String::from).collect()
+    }
+
+    fn properties(schema: &serde_json::Value) -> BTreeSet<String> {
+        schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_matches_model() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../docs/schema/schema.json")).unwrap();
+
+        assert_eq!(properties(&schema), fields::<Package>());
+        assert_eq!(
+            properties(&schema["properties"]["build"]),
+            fields::<Build>()
+        );
+        assert_eq!(
+            properties(&schema["properties"]["livecheck"]),
+            fields::<Livecheck>()
+        );
+        assert_eq!(
+            properties(&schema["properties"]["tests"]["items"]),
+            fields::<TestCase>()
+        );
+
+        // Source and platform downloads share one model
+        let mut downloads =
+            properties(&schema["properties"]["downloads"]["properties"]["source"]);
+        downloads.extend(properties(&schema["definitions"]["platformDownload"]));
+        assert_eq!(downloads, fields::<Download>());
+
+        let types: BTreeSet<String> = schema["properties"]["type"]["enum"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap().to_string())
+            .collect();
+        for t in &types {
+            let parsed: PackageType = serde_json::from_value(t.as_str().into()).unwrap();
+            assert_eq!(&parsed.to_string(), t);
+        }
+    }
+
+    #[test]
+    fn test_package_from_json() {
+        let pkg = Package::from_json(
+            r##"{
+                "name": "demo",
+                "version": "1.0",
+                "description": "Demo",
+                "homepage": "https://example.com",
+                "license": "MIT",
+                "type": "prebuild",
+                "downloads": {
+                    "linux": {
+                        "url": "https://example.com/demo.tar.gz",
+                        "binary": "demo",
+                        "clean": "docs",
+                        "wrapper": {"demo-sh": ["#!/bin/sh", "demo \"$@\""]}
+                    }
+                },
+                "tests": [
+                    {"name": "version", "command": "demo", "pattern": "1.0", "args": "--version"}
+                ]
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(pkg.pkg_type, PackageType::Prebuild);
+        let linux = pkg.download("linux").unwrap();
+        assert_eq!(linux.binary, vec!["demo"]);
+        assert_eq!(linux.clean, vec!["docs"]);
+        assert_eq!(
+            linux.wrapper["demo-sh"],
+            Script("#!/bin/sh\ndemo \"$@\"\n".to_string())
+        );
+        assert_eq!(pkg.tests[0].args, vec!["--version"]);
+        assert!(pkg.download("macos").is_err());
+
+        let source: Download = serde_json::from_str(
+            r#"{"url": "x", "patches": ["zig.patch", {"file": "gcc.patch", "strip": 0}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            source.patches,
+            vec![
+                Patch {
+                    file: "zig.patch".to_string(),
+                    strip: 1
+                },
+                Patch {
+                    file: "gcc.patch".to_string(),
+                    strip: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_git_source() {
+        let base = r#""name": "demo", "version": "1.0", "description": "Demo",
+            "homepage": "https://example.com", "license": "MIT", "type": "make""#;
+
+        let pkg = Package::from_json(&format!(
+            r#"{{{}, "downloads": {{"source": {{"git": "https://example.com/demo.git", "rev": "v1.0"}}}}}}"#,
+            base
+        ))
+        .unwrap();
+        let source = pkg.download("source").unwrap();
+        assert_eq!(source.rev.as_deref(), Some("v1.0"));
+        assert!(!source.is_url_only());
+
+        let err = Package::from_json(&format!(
+            r#"{{{}, "downloads": {{"source": {{"git": "https://example.com/demo.git"}}}}}}"#,
+            base
+        ))
+        .unwrap_err()
+        .to_string();
+        assert_eq!(err, "downloads.source.rev: required with `git`");
+
+        let err = Package::from_json(&format!(
+            r#"{{{}, "downloads": {{"source": {{"sha256": "x"}}}}}}"#,
+            base
+        ))
+        .unwrap_err()
+        .to_string();
+        assert_eq!(err, "downloads.source: missing field `url`");
+    }
+
+    #[test]
+    fn test_render_templates() {
+        let mut pkg = Package::from_json(
+            r#"{
+                "name": "bat", "version": "0.25.0", "description": "Demo",
+                "homepage": "https://example.com", "license": "MIT", "type": "prebuild",
+                "downloads": {
+                    "linux": {
+                        "url": "https://example.com/v{{version}}/bat-v{{version}}.tar.gz",
+                        "binary": "bat-v{{version}}-*/bat",
+                        "resources": [{
+                            "url": "https://example.com/v{{version}}/data.tar.gz",
+                            "dest": "data"
+                        }]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        pkg.render_templates();
+
+        let linux = pkg.download("linux").unwrap();
+        assert_eq!(
+            linux.url(),
+            "https://example.com/v0.25.0/bat-v0.25.0.tar.gz"
+        );
+        assert_eq!(linux.binary, vec!["bat-v0.25.0-*/bat"]);
+        assert_eq!(
+            linux.resources[0].download.url(),
+            "https://example.com/v0.25.0/data.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_package_errors() {
+        let base = r#""name": "demo", "version": "1.0", "description": "Demo",
+            "homepage": "https://example.com", "license": "MIT""#;
+
+        let err = Package::from_json(&format!(r#"{{{}, "type": "bogus"}}"#, base))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("type: unknown variant `bogus`"), "{}", err);
+
+        let err = Package::from_json(&format!(
+            r#"{{{}, "type": "prebuild", "downloads": {{"linux": {{"url": "x", "binary": 1}}}}}}"#,
+            base
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.starts_with("downloads.linux.binary: invalid type: integer `1`"),
+            "{}",
+            err
+        );
+
+        let err = Package::from_json(&format!(
+            r#"{{{}, "type": "rust", "tests": [{{"name": "t", "pattern": "p"}}]}}"#,
+            base
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.starts_with("tests[0]: missing field `command`"),
+            "{}",
+            err
+        );
+
+        let err = Package::from_json(r#"{"name": "demo"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("missing field `version`"), "{}", err);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::Read;
+use std::path::Path;
+
+/// Placeholder for the cbp home in packaged files, substituted on install
+pub const PREFIX_PLACEHOLDER: &str = "@@CBP_PREFIX@@";
+
+/// Check if a package path is a pkg-config, libtool or CMake config file,
+/// which embed the absolute prefix they were built for
+pub fn is_prefix_config(path: &str) -> bool {
+    path.ends_with(".pc")
+        || path.ends_with(".la")
+        || (path.ends_with(".cmake")
+            && (path.starts_with("cmake/") || path.contains("/cmake/")))
+}
+
+/// Replace build prefixes in the config files under `dir` with
+/// [`PREFIX_PLACEHOLDER`]
+///
+/// Returns the relative paths of rewritten files.
+pub fn relocate_configs(dir: &Path, prefixes: &[String]) -> anyhow::Result<Vec<String>> {
+    // Longest first, so nested prefixes are not cut short
+    let mut prefixes: Vec<&str> = prefixes
+        .iter()
+        .map(|p| p.trim_end_matches(['/', '\\']))
+        .filter(|p| !p.is_empty())
+        .collect();
+    prefixes.sort_by_key(|p| std::cmp::Reverse(p.len()));
+
+    let mut relocated = vec![];
+    for file in crate::find_files(dir, None)? {
+        let path = dir.join(&file);
+        if !is_prefix_config(&file) || path.is_symlink() {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let mut new_content = content.clone();
+        for prefix in &prefixes {
+            new_content = new_content.replace(prefix, PREFIX_PLACEHOLDER);
+        }
+        if new_content != content {
+            std::fs::write(&path, new_content)?;
+            relocated.push(file);
+        }
+    }
+    Ok(relocated)
+}
+
+/// Replace [`PREFIX_PLACEHOLDER`] with `home` in installed text files
+///
+/// `files` are paths relative to `home`, as listed in `records/{pkg}.files`.
+/// Scripts, wrappers and config files are all covered; binaries are left alone.
+pub fn substitute_prefix(home: &Path, files: &[&str]) -> anyhow::Result<Vec<String>> {
+    let native = home.to_string_lossy().to_string();
+    // Forward slashes are understood by pkg-config and CMake on Windows too
+    let forward = native.replace('\\', "/");
+
+    let mut substituted = vec![];
+    for file in files {
+        let path = home.join(file);
+        if !path.is_file() || path.is_symlink() || !has_placeholder(&path)? {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let prefix = if is_prefix_config(file) {
+            &forward
+        } else {
+            &native
+        };
+        std::fs::write(&path, content.replace(PREFIX_PLACEHOLDER, prefix))?;
+        substituted.push(file.to_string());
+    }
+    Ok(substituted)
+}
+
+/// Check if a text file holds [`PREFIX_PLACEHOLDER`]
+///
+/// Files are scanned block by block, stopping at the placeholder. Binaries, with
+/// a NUL byte in their first block as git decides, are given up after it.
+fn has_placeholder(path: &Path) -> anyhow::Result<bool> {
+    let needle = PREFIX_PLACEHOLDER.as_bytes();
+    let mut file = std::fs::File::open(path)?;
+    let mut window = Vec::with_capacity(8000 + needle.len());
+    let mut first = true;
+    loop {
+        // Keep the tail of the last block, the placeholder may span two
+        let keep = window.len().min(needle.len() - 1);
+        window.drain(..window.len() - keep);
+        let read = (&mut file).take(8000).read_to_end(&mut window)?;
+
+        if first && window.contains(&0) {
+            return Ok(false);
+        }
+        first = false;
+        if window.windows(needle.len()).any(|w| w == needle) {
+            return Ok(true);
+        }
+        if read == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_prefix_config() {
+        assert!(is_prefix_config("lib/pkgconfig/htslib.pc"));
+        assert!(is_prefix_config("lib/libhts.la"));
+        assert!(is_prefix_config("lib/cmake/ZLIB/ZLIBConfig.cmake"));
+        assert!(is_prefix_config("share/cmake/fmt/fmt-targets.cmake"));
+        assert!(!is_prefix_config("share/doc/readme.cmake"));
+        assert!(!is_prefix_config("lib/libhts.a"));
+    }
+
+    #[test]
+    fn test_has_placeholder() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file = temp_dir.path().join("file");
+
+        // Across the first two blocks, and far behind them
+        for offset in [7995, 20000] {
+            std::fs::write(
+                &file,
+                format!("{}{}", "x".repeat(offset), PREFIX_PLACEHOLDER),
+            )?;
+            assert!(has_placeholder(&file)?);
+        }
+        std::fs::write(&file, "x".repeat(20000))?;
+        assert!(!has_placeholder(&file)?);
+        let mut binary = b"\0".to_vec();
+        binary.extend_from_slice(PREFIX_PLACEHOLDER.as_bytes());
+        std::fs::write(&file, binary)?;
+        assert!(!has_placeholder(&file)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_and_substitute() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("lib/pkgconfig"))?;
+        std::fs::write(
+            dir.join("lib/pkgconfig/hts.pc"),
+            "prefix=/build/installed/x64-linux\nlibdir=/build/installed/x64-linux/lib\n",
+        )?;
+        std::fs::write(dir.join("lib/notes.txt"), "/build/installed/x64-linux\n")?;
+        std::fs::create_dir_all(dir.join("bin"))?;
+        std::fs::write(
+            dir.join("bin/tool"),
+            "#!/bin/sh\nexec @@CBP_PREFIX@@/libexec/tool\n",
+        )?;
+        std::fs::write(dir.join("bin/tool.bin"), b"\0@@CBP_PREFIX@@\0")?;
+
+        let relocated =
+            relocate_configs(dir, &["/build/installed/x64-linux/".to_string()])?;
+        assert_eq!(relocated, vec!["lib/pkgconfig/hts.pc"]);
+        let content = std::fs::read_to_string(dir.join("lib/pkgconfig/hts.pc"))?;
+        assert_eq!(
+            content,
+            "prefix=@@CBP_PREFIX@@\nlibdir=@@CBP_PREFIX@@/lib\n"
+        );
+
+        let files = [
+            "bin/tool",
+            "bin/tool.bin",
+            "lib/notes.txt",
+            "lib/pkgconfig/hts.pc",
+        ];
+        let substituted = substitute_prefix(dir, &files)?;
+        assert_eq!(substituted, vec!["bin/tool", "lib/pkgconfig/hts.pc"]);
+        let content = std::fs::read_to_string(dir.join("lib/pkgconfig/hts.pc"))?;
+        let home = dir.to_string_lossy().replace('\\', "/");
+        assert!(content.starts_with(&format!("prefix={}\n", home)));
+        let content = std::fs::read_to_string(dir.join("bin/tool"))?;
+        assert!(content.contains(&format!("exec {}/libexec/tool", dir.display())));
+        assert_eq!(
+            std::fs::read(dir.join("bin/tool.bin"))?,
+            b"\0@@CBP_PREFIX@@\0"
+        );
+        Ok(())
+    }
+}
@@ -231,6 +231,54 @@ pub fn find_files(dir: &Path, pattern: Option<&str>) -> anyhow::Result<Vec<Strin
     Ok(files)
 }
 
+/// Glob or regex pattern for package paths
+///
+/// Patterns starting with `re:` are regexes searched anywhere in the path.
+/// Globs without `/` match a single path component, so `test` drops `test/`
+/// but not `latest/`; other globs match the whole path or a parent directory.
+#[derive(Debug, Clone)]
+pub enum PathPattern {
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl std::str::FromStr for PathPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("re:") {
+            Some(re) => regex::Regex::new(re)
+                .map(PathPattern::Regex)
+                .map_err(|e| anyhow::anyhow!("Invalid regex {}: {}", re, e)),
+            None => glob::Pattern::new(s.trim_end_matches('/'))
+                .map(PathPattern::Glob)
+                .map_err(|e| anyhow::anyhow!("Invalid glob {}: {}", s, e)),
+        }
+    }
+}
+
+impl PathPattern {
+    /// Check a relative path with forward slashes
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            PathPattern::Regex(re) => re.is_match(path),
+            PathPattern::Glob(pat) => {
+                let options = glob::MatchOptions {
+                    require_literal_separator: true,
+                    ..Default::default()
+                };
+                let parts: Vec<&str> = path.split('/').collect();
+                if pat.as_str().contains('/') {
+                    (1..=parts.len())
+                        .any(|i| pat.matches_with(&parts[..i].join("/"), options))
+                } else {
+                    parts.iter().any(|part| pat.matches_with(part, options))
+                }
+            }
+        }
+    }
+}
+
 /// Match files using glob pattern and return matching paths
 pub fn match_files(
     base_path: &std::path::Path,
@@ -252,6 +300,23 @@ pub fn match_files(
     Ok(matches)
 }
 
+/// Resolve a command name to its executable path under `bin_dir`
+///
+/// On Windows, common executable extensions (`.exe`, `.ps1`, `.bat`, `.cmd`)
+/// are tried when the bare name does not exist
+pub fn resolve_bin_path(bin_dir: &Path, cmd: &str) -> PathBuf {
+    let cmd_path = bin_dir.join(cmd);
+    if !cfg!(windows) || cmd_path.exists() {
+        return cmd_path;
+    }
+
+    ["exe", "ps1", "bat", "cmd"]
+        .iter()
+        .map(|ext| cmd_path.with_extension(ext))
+        .find(|path| path.exists())
+        .unwrap_or(cmd_path)
+}
+
 /// Check if a file is managed by cbp itself
 pub fn is_cbp_file(path: &str) -> bool {
     path.starts_with("bin/cbp")
@@ -259,6 +324,7 @@ pub fn is_cbp_file(path: &str) -> bool {
         || path.starts_with("records/")
         || path.starts_with("cache/")
         || path.starts_with("triplets/")
+        || path == "mirrors.json"
 }
 
 /// Check if a file should be ignored based on system patterns
@@ -305,6 +371,46 @@ pub fn github_release_url() -> String {
         .unwrap_or_else(|_| "https://github.com".to_string())
 }
 
+/// Returns URL prefix rewrite rules for mirrors
+///
+/// Rules are read from a JSON object mapping prefixes to replacements, e.g.
+/// `{"https://github.com": "https://proxy.example.com/github"}`, in the file named
+/// by the `CBP_MIRRORS` env var or `mirrors.json` in the cbp `home`.
+/// `GITHUB_RELEASE_URL` adds a rule for `https://github.com`, taking precedence
+/// over the file.
+pub fn mirror_rules(home: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let path = match std::env::var("CBP_MIRRORS") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => home.join("mirrors.json"),
+    };
+
+    let mut rules = std::collections::BTreeMap::new();
+    if path.is_file() {
+        let content = std::fs::read_to_string(&path)?;
+        let map: std::collections::BTreeMap<String, String> =
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))?;
+        rules.extend(map);
+    }
+    if let Ok(url) = std::env::var("GITHUB_RELEASE_URL") {
+        rules.insert("https://github.com".to_string(), url);
+    }
+
+    Ok(rules.into_iter().collect())
+}
+
+/// Rewrite a URL by the longest matching prefix of the mirror rules
+pub fn rewrite_url(url: &str, home: &Path) -> anyhow::Result<String> {
+    let rules = mirror_rules(home)?;
+    let rewritten = rules
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, replacement)| format!("{}{}", replacement, &url[prefix.len()..]));
+
+    Ok(rewritten.unwrap_or_else(|| url.to_string()))
+}
+
 /// Returns the base URL for GitHub API, respecting `GITHUB_API_URL` env var
 pub fn github_api_url() -> String {
     std::env::var("GITHUB_API_URL")
@@ -317,24 +423,145 @@ pub fn github_raw_url() -> String {
         .unwrap_or_else(|_| "https://raw.githubusercontent.com".to_string())
 }
 
-/// List files in a tar.gz archive
+/// Fetch a package definition JSON from the GitHub repository
+pub fn fetch_package_json(
+    agent: &ureq::Agent,
+    pkg: &str,
+) -> anyhow::Result<serde_json::Value> {
+    let json_url = format!(
+        "{}/wang-q/cbp/master/packages/{}.json",
+        github_raw_url(),
+        pkg
+    );
+    let resp = agent
+        .get(&json_url)
+        .set("user-agent", "cbp")
+        .call()?
+        .into_string()?;
+
+    Ok(serde_json::from_str(&resp)?)
+}
+
+/// UTC build time as `YYYY-MM-DDTHH:MM:SSZ`
+///
+/// Honors `SOURCE_DATE_EPOCH` so that builds can be reproduced.
+pub fn build_timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        });
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's algorithm
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Calculate the SHA-256 digest of a file as a lowercase hex string
+pub fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compare two version strings component by component
+///
+/// A leading `v` is ignored. Numeric components are compared as numbers,
+/// other components as strings, e.g. `1.10` > `1.9` and `1.3.1` > `1.3`.
+pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    fn parts(v: &str) -> Vec<String> {
+        let v = v.trim().trim_start_matches(['v', 'V']);
+        let re = regex::Regex::new(r"\d+|[a-zA-Z]+").unwrap();
+        re.find_iter(v).map(|m| m.as_str().to_string()).collect()
+    }
+
+    let (pa, pb) = (parts(a), parts(b));
+    for (x, y) in pa.iter().zip(pb.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ord != std::cmp::Ordering::Equal {
+            return ord;
+        }
+    }
+    pa.len().cmp(&pb.len())
+}
+
+/// Replace whole occurrences of the version `old` in `s` with `new`
+///
+/// Occurrences inside a longer version are kept, so for `1.3` both
+/// `pkg-1.3.tar.gz` and `v1.3/` change, but `1.30`, `11.3` and `1.3.1` do not.
+pub fn replace_version_token(s: &str, old: &str, new: &str) -> String {
+    if old.is_empty() {
+        return s.to_string();
+    }
+    // A digit, or a dot and a digit, continues the version
+    fn continues(mut chars: impl Iterator<Item = char>) -> bool {
+        let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+        let first = chars.next();
+        is_digit(first) || (first == Some('.') && is_digit(chars.next()))
+    }
+
+    let mut result = String::with_capacity(s.len());
+    let mut last = 0;
+    for (start, _) in s.match_indices(old) {
+        let end = start + old.len();
+        if continues(s[..start].chars().rev()) || continues(s[end..].chars()) {
+            continue;
+        }
+        result.push_str(&s[last..start]);
+        result.push_str(new);
+        last = end;
+    }
+    result.push_str(&s[last..]);
+    result
+}
+
+/// List files in a tarball, compressed with gzip, zstd or xz
 ///
 /// # Arguments
 ///
-/// * `archive_path` - Path to the tar.gz file
+/// * `archive_path` - Path to the tarball
 ///
 /// # Returns
 ///
 /// A list of file paths in the archive, one per line
 pub fn list_archive_files(archive_path: &Path) -> anyhow::Result<String> {
-    let file = std::fs::File::open(archive_path)?;
-    let gz = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(gz);
+    let mut archive = crate::open_archive(archive_path)?;
 
     let mut file_list = String::new();
     let entries = archive.entries()?;
     for entry in entries {
         let entry = entry?;
+        if entry.header().entry_type() == tar::EntryType::XGlobalHeader {
+            continue;
+        }
         if let Some(path) = entry.path()?.to_str() {
             file_list.push_str(path);
             file_list.push('\n');
@@ -344,14 +571,12 @@ pub fn list_archive_files(archive_path: &Path) -> anyhow::Result<String> {
     Ok(file_list)
 }
 
-/// Read file content from a tar.gz archive
+/// Read file content from a tarball
 pub fn read_file_from_archive(
     archive_path: &std::path::Path,
     file_path: &str,
 ) -> anyhow::Result<String> {
-    let file = std::fs::File::open(archive_path)?;
-    let tar = flate2::read::GzDecoder::new(file);
-    let mut archive = tar::Archive::new(tar);
+    let mut archive = crate::open_archive(archive_path)?;
 
     for entry in archive.entries()? {
         let mut entry = entry?;
@@ -365,10 +590,216 @@ pub fn read_file_from_archive(
     Err(anyhow::anyhow!("File not found in archive: {}", file_path))
 }
 
+/// Read the comment of a snapshot or package archive
+///
+/// Gzip archives keep it in the gzip header, zstd and xz ones in a pax global
+/// header.
+pub fn read_comment(path: &Path) -> anyhow::Result<String> {
+    use std::io::Read;
+
+    if crate::CompressionFormat::detect(path)? != Some(crate::CompressionFormat::Gzip) {
+        return Ok(crate::read_tar_comment(path)?.unwrap_or_default());
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut decoder = flate2::read::GzDecoder::new(file);
+    let mut buf = Vec::new();
+    decoder.read_to_end(&mut buf)?;
+    let header = decoder.header();
+    Ok(header
+        .and_then(|h| h.comment())
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .unwrap_or_default())
+}
+
+/// Format byte size to human readable string
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{}B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1}K", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1}M", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1}G", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    }
+}
+
+/// Find the target path for an archive entry based on source paths
+/// Returns the absolute path where the entry should be extracted
+pub fn find_target_path(
+    archive_entry: &Path,
+    source_paths: &[String],
+    home: &Path,
+) -> Option<PathBuf> {
+    let entry_str = archive_entry.to_string_lossy().to_string();
+
+    // Try to match against each source path
+    for source in source_paths {
+        let source_path = expand_home_path(source, home);
+        let source_name = source_path.file_name()?.to_string_lossy();
+
+        // Check if entry starts with the source name
+        if entry_str.starts_with(&*source_name) {
+            // Get the relative part after the source name
+            let rel_part = entry_str.strip_prefix(&*source_name)?;
+            let rel_part = rel_part
+                .strip_prefix('/')
+                .or_else(|| rel_part.strip_prefix('\\'))
+                .unwrap_or(rel_part);
+
+            // Build the full target path
+            // If rel_part is empty, return source_path (single file case)
+            // Otherwise, join rel_part to source_path (directory case)
+            let target = if rel_part.is_empty() {
+                source_path
+            } else {
+                source_path.join(rel_part)
+            };
+            return Some(target);
+        }
+    }
+
+    // Fallback: try to construct path from home
+    Some(home.join(&entry_str))
+}
+
+/// Generate delta snapshot output name from archive path
+/// Converts "name.tar.gz" or "name.snap.tar.zst" to "name.delta.tar.gz" or
+/// "name.delta.tar.zst", following `compression`
+pub fn delta_output_name(
+    archive: &Path,
+    compression: crate::CompressionFormat,
+) -> String {
+    let stem = archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+    let stem = stem
+        .strip_suffix(".tar")
+        .unwrap_or(&stem)
+        .strip_suffix(".snap")
+        .unwrap_or(&stem);
+    format!("{}.delta.{}", stem, compression.extension())
+}
+
+/// Find matching source path for a display path
+/// Used when packing modified files to determine archive structure
+pub fn find_matching_source(
+    display_path: &Path,
+    source_paths: &[String],
+) -> Option<String> {
+    let display = display_path.to_string_lossy().to_string();
+    let display = if display.starts_with('/') || display.starts_with('\\') {
+        display
+    } else {
+        format!("/{}", display)
+    };
+
+    for source in source_paths {
+        let source_no_tilde = source.strip_prefix('~').unwrap_or(source);
+        if display.starts_with(source_no_tilde) || display.contains(source_no_tilde) {
+            return Some(source.clone());
+        }
+    }
+    source_paths.first().cloned()
+}
+
+/// Generate font installation instructions for the current OS
+pub fn font_install_instructions(os_type: &str, font_dir: &Path) -> String {
+    let mut result = String::new();
+    result.push_str("==> To install fonts for current user, run:\n\n");
+
+    match os_type {
+        "windows" => {
+            result.push_str(
+                "$fonts = (New-Object -ComObject Shell.Application).Namespace(0x14)\n",
+            );
+            result.push_str(&format!(
+                "Get-ChildItem \"{}\" -Include '*.ttf','*.ttc','*.otf' -Recurse | ForEach {{\n",
+                font_dir.display()
+            ));
+            result.push_str(
+                "    If (-not(Test-Path \"C:\\Windows\\Fonts\\$($_.Name)\") -and\n",
+            );
+            result.push_str("        -not(Test-Path \"$env:LOCALAPPDATA\\Microsoft\\Windows\\Fonts\\$($_.Name)\")) {\n");
+            result.push_str("        $fonts.CopyHere($_.FullName, 0x10)\n");
+            result.push_str("        Write-Host \"Installing $($_.Name)...\"\n");
+            result.push_str("    }\n");
+            result.push_str("}\n");
+        }
+        "macos" => {
+            result.push_str("for ext in ttf ttc otf; do\n");
+            result.push_str(&format!(
+                "    find \"{}\" -type f -iname \"*.$ext\" -print0 | while IFS= read -r -d '' font; do\n",
+                font_dir.display()
+            ));
+            result.push_str("        basename=\"$(basename \"$font\")\"\n");
+            result.push_str(
+                "        if [ ! -f \"$HOME/Library/Fonts/$basename\" ]; then\n",
+            );
+            result.push_str("            cp \"$font\" \"$HOME/Library/Fonts/\"\n");
+            result.push_str("            echo \"Installing $basename...\"\n");
+            result.push_str("        fi\n");
+            result.push_str("    done\n");
+            result.push_str("done\n");
+        }
+        "linux" => {
+            result.push_str("mkdir -p \"$HOME/.local/share/fonts\"\n");
+            result.push_str("for ext in ttf ttc otf; do\n");
+            result.push_str(&format!(
+                "    find \"{}\" -type f -iname \"*.$ext\" -print0 | while IFS= read -r -d '' font; do\n",
+                font_dir.display()
+            ));
+            result.push_str("        basename=\"$(basename \"$font\")\"\n");
+            result.push_str(
+                "        if [ ! -f \"$HOME/.local/share/fonts/$basename\" ]; then\n",
+            );
+            result.push_str("            cp \"$font\" \"$HOME/.local/share/fonts/\"\n");
+            result.push_str("            echo \"Installing $basename...\"\n");
+            result.push_str("        fi\n");
+            result.push_str("    done\n");
+            result.push_str("done\n");
+            result.push_str("fc-cache -f -v\n");
+        }
+        _ => {}
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_path_pattern() -> anyhow::Result<()> {
+        let pat: PathPattern = "test".parse()?;
+        assert!(pat.matches("test/data.txt"));
+        assert!(pat.matches("src/test"));
+        assert!(!pat.matches("latest/data.txt"));
+        assert!(!pat.matches("include/contest.h"));
+
+        let pat: PathPattern = "*.dll".parse()?;
+        assert!(pat.matches("bin/zlib.dll"));
+        assert!(!pat.matches("bin/zlib.dll.a"));
+
+        let pat: PathPattern = "share/doc".parse()?;
+        assert!(pat.matches("share/doc/README"));
+        assert!(!pat.matches("doc/share/doc"));
+
+        let pat: PathPattern = "lib/*.la".parse()?;
+        assert!(pat.matches("lib/libz.la"));
+        assert!(!pat.matches("lib/pkgconfig/zlib.la"));
+
+        let pat: PathPattern = r"re:\.so(\.\d+)*$".parse()?;
+        assert!(pat.matches("lib/libz.so.1"));
+        assert!(!pat.matches("lib/libz.a"));
+
+        assert!("re:(".parse::<PathPattern>().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_get_os_type() {
         // Since std::env::consts::OS is a compile-time constant,
@@ -689,177 +1120,64 @@ mod tests {
     }
 
     #[test]
-    fn test_format_size() {
-        assert_eq!(format_size(500), "500B");
-        assert_eq!(format_size(1024), "1.0K");
-        assert_eq!(format_size(1024 * 1024), "1.0M");
-        assert_eq!(format_size(1024 * 1024 * 1024), "1.0G");
+    fn test_build_timestamp() {
+        std::env::set_var("SOURCE_DATE_EPOCH", "1704067200");
+        assert_eq!(build_timestamp(), "2024-01-01T00:00:00Z");
+        std::env::set_var("SOURCE_DATE_EPOCH", "951827696");
+        assert_eq!(build_timestamp(), "2000-02-29T12:34:56Z");
+        std::env::remove_var("SOURCE_DATE_EPOCH");
+        assert!(build_timestamp().ends_with('Z'));
     }
-}
 
-/// Read gzip comment from a snapshot archive
-pub fn read_comment(path: &Path) -> anyhow::Result<String> {
-    use std::io::Read;
-
-    let file = std::fs::File::open(path)?;
-    let mut decoder = flate2::read::GzDecoder::new(file);
-    let mut buf = Vec::new();
-    decoder.read_to_end(&mut buf)?;
-    let header = decoder.header();
-    Ok(header
-        .and_then(|h| h.comment())
-        .map(|c| String::from_utf8_lossy(c).to_string())
-        .unwrap_or_default())
-}
-
-/// Format byte size to human readable string
-pub fn format_size(bytes: u64) -> String {
-    if bytes < 1024 {
-        format!("{}B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1}K", bytes as f64 / 1024.0)
-    } else if bytes < 1024 * 1024 * 1024 {
-        format!("{:.1}M", bytes as f64 / (1024.0 * 1024.0))
-    } else {
-        format!("{:.1}G", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    #[test]
+    fn test_compare_versions() {
+        use std::cmp::Ordering;
+
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("v0.4.0", "0.4.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.4.0", "0.4.1"), Ordering::Less);
+        assert_eq!(compare_versions("2.4pre", "2.3"), Ordering::Greater);
+        assert_eq!(compare_versions("1.3", "1.3.1"), Ordering::Less);
     }
-}
-
-/// Find the target path for an archive entry based on source paths
-/// Returns the absolute path where the entry should be extracted
-pub fn find_target_path(
-    archive_entry: &Path,
-    source_paths: &[String],
-    home: &Path,
-) -> Option<PathBuf> {
-    let entry_str = archive_entry.to_string_lossy().to_string();
 
-    // Try to match against each source path
-    for source in source_paths {
-        let source_path = expand_home_path(source, home);
-        let source_name = source_path.file_name()?.to_string_lossy();
-
-        // Check if entry starts with the source name
-        if entry_str.starts_with(&*source_name) {
-            // Get the relative part after the source name
-            let rel_part = entry_str.strip_prefix(&*source_name)?;
-            let rel_part = rel_part
-                .strip_prefix('/')
-                .or_else(|| rel_part.strip_prefix('\\'))
-                .unwrap_or(rel_part);
-
-            // Build the full target path
-            // If rel_part is empty, return source_path (single file case)
-            // Otherwise, join rel_part to source_path (directory case)
-            let target = if rel_part.is_empty() {
-                source_path
-            } else {
-                source_path.join(rel_part)
-            };
-            return Some(target);
-        }
+    #[test]
+    fn test_replace_version_token() {
+        assert_eq!(
+            replace_version_token("v1.3/pkg-1.3.tar.gz", "1.3", "1.4"),
+            "v1.4/pkg-1.4.tar.gz"
+        );
+        assert_eq!(
+            replace_version_token("v1.30/pkg-1.30.tar.gz", "1.3", "1.4"),
+            "v1.30/pkg-1.30.tar.gz"
+        );
+        assert_eq!(replace_version_token("pkg-11.3", "1.3", "1.4"), "pkg-11.3");
+        assert_eq!(
+            replace_version_token("pkg-1.3.1", "1.3", "1.4"),
+            "pkg-1.3.1"
+        );
+        assert_eq!(
+            replace_version_token("pkg_1.3_x64", "1.3", "1.4"),
+            "pkg_1.4_x64"
+        );
     }
 
-    // Fallback: try to construct path from home
-    Some(home.join(&entry_str))
-}
-
-/// Generate delta snapshot output name from archive path
-/// Converts "name.tar.gz" or "name.snap.tar.gz" to "name.delta.tar.gz"
-pub fn delta_output_name(archive: &Path) -> String {
-    let stem = archive
-        .file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "archive".to_string());
-    let stem = stem
-        .strip_suffix(".tar")
-        .unwrap_or(&stem)
-        .strip_suffix(".snap")
-        .unwrap_or(&stem);
-    format!("{}.delta.tar.gz", stem)
-}
-
-/// Find matching source path for a display path
-/// Used when packing modified files to determine archive structure
-pub fn find_matching_source(
-    display_path: &Path,
-    source_paths: &[String],
-) -> Option<String> {
-    let display = display_path.to_string_lossy().to_string();
-    let display = if display.starts_with('/') || display.starts_with('\\') {
-        display
-    } else {
-        format!("/{}", display)
-    };
-
-    for source in source_paths {
-        let source_no_tilde = source.strip_prefix('~').unwrap_or(source);
-        if display.starts_with(source_no_tilde) || display.contains(source_no_tilde) {
-            return Some(source.clone());
-        }
+    #[test]
+    fn test_sha256_file() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let file = tmp.path().join("hello.txt");
+        std::fs::write(&file, "hello")?;
+        assert_eq!(
+            sha256_file(&file)?,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        Ok(())
     }
-    source_paths.first().cloned()
-}
 
-/// Generate font installation instructions for the current OS
-pub fn font_install_instructions(os_type: &str, font_dir: &Path) -> String {
-    let mut result = String::new();
-    result.push_str("==> To install fonts for current user, run:\n\n");
-
-    match os_type {
-        "windows" => {
-            result.push_str(
-                "$fonts = (New-Object -ComObject Shell.Application).Namespace(0x14)\n",
-            );
-            result.push_str(&format!(
-                "Get-ChildItem \"{}\" -Include '*.ttf','*.ttc','*.otf' -Recurse | ForEach {{\n",
-                font_dir.display()
-            ));
-            result.push_str(
-                "    If (-not(Test-Path \"C:\\Windows\\Fonts\\$($_.Name)\") -and\n",
-            );
-            result.push_str("        -not(Test-Path \"$env:LOCALAPPDATA\\Microsoft\\Windows\\Fonts\\$($_.Name)\")) {\n");
-            result.push_str("        $fonts.CopyHere($_.FullName, 0x10)\n");
-            result.push_str("        Write-Host \"Installing $($_.Name)...\"\n");
-            result.push_str("    }\n");
-            result.push_str("}\n");
-        }
-        "macos" => {
-            result.push_str("for ext in ttf ttc otf; do\n");
-            result.push_str(&format!(
-                "    find \"{}\" -type f -iname \"*.$ext\" -print0 | while IFS= read -r -d '' font; do\n",
-                font_dir.display()
-            ));
-            result.push_str("        basename=\"$(basename \"$font\")\"\n");
-            result.push_str(
-                "        if [ ! -f \"$HOME/Library/Fonts/$basename\" ]; then\n",
-            );
-            result.push_str("            cp \"$font\" \"$HOME/Library/Fonts/\"\n");
-            result.push_str("            echo \"Installing $basename...\"\n");
-            result.push_str("        fi\n");
-            result.push_str("    done\n");
-            result.push_str("done\n");
-        }
-        "linux" => {
-            result.push_str("mkdir -p \"$HOME/.local/share/fonts\"\n");
-            result.push_str("for ext in ttf ttc otf; do\n");
-            result.push_str(&format!(
-                "    find \"{}\" -type f -iname \"*.$ext\" -print0 | while IFS= read -r -d '' font; do\n",
-                font_dir.display()
-            ));
-            result.push_str("        basename=\"$(basename \"$font\")\"\n");
-            result.push_str(
-                "        if [ ! -f \"$HOME/.local/share/fonts/$basename\" ]; then\n",
-            );
-            result.push_str("            cp \"$font\" \"$HOME/.local/share/fonts/\"\n");
-            result.push_str("            echo \"Installing $basename...\"\n");
-            result.push_str("        fi\n");
-            result.push_str("    done\n");
-            result.push_str("done\n");
-            result.push_str("fc-cache -f -v\n");
-        }
-        _ => {}
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(500), "500B");
+        assert_eq!(format_size(1024), "1.0K");
+        assert_eq!(format_size(1024 * 1024), "1.0M");
+        assert_eq!(format_size(1024 * 1024 * 1024), "1.0G");
     }
-
-    result
 }
@@ -23,6 +23,20 @@ fn command_build_validate() -> anyhow::Result<()> {
         }"#,
     )?;
 
+    // Create a package with a wrongly typed field
+    std::fs::write(
+        temp_dir.path().join("packages/typo.json"),
+        r#"{
+            "name": "typo",
+            "version": "1.0",
+            "description": "Typo",
+            "homepage": "https://example.com",
+            "license": "MIT",
+            "type": "prebuild",
+            "downloads": {"linux": {"url": "https://example.com/a.tar.gz", "binary": 1}}
+        }"#,
+    )?;
+
     // Test validating a valid package
     Command::cargo_bin("cbp")?
         .arg("build")
@@ -45,6 +59,19 @@ fn command_build_validate() -> anyhow::Result<()> {
         .failure()
         .stdout(predicate::str::contains("Validating invalid... FAILED"));
 
+    // Errors point at the offending field
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("validate")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("typo")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "downloads.linux.binary: invalid type",
+        ));
+
     // Test validating a non-existent package
     Command::cargo_bin("cbp")?
         .arg("build")
@@ -125,3 +152,623 @@ fn command_build_test_font() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Create `sources/{pkg}.tar.gz` with the given files under `{pkg}-1.0/`
+///
+/// Files starting with `#!` are made executable.
+fn create_source_archive(
+    base: &std::path::Path,
+    pkg: &str,
+    files: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(base.join("sources"))?;
+    let file = std::fs::File::create(base.join(format!("sources/{}.tar.gz", pkg)))?;
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        file,
+        flate2::Compression::default(),
+    ));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(if content.starts_with("#!") {
+            0o755
+        } else {
+            0o644
+        });
+        header.set_cksum();
+        archive.append_data(
+            &mut header,
+            format!("{}-1.0/{}", pkg, name),
+            content.as_bytes(),
+        )?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn command_build_compile() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path();
+    let os_type = cbp::get_os_type()?;
+
+    std::fs::create_dir_all(base.join("packages"))?;
+    std::fs::write(
+        base.join("packages/hello.json"),
+        r#"{
+            "name": "hello",
+            "version": "1.0",
+            "description": "Hello world",
+            "homepage": "https://example.com",
+            "license": "MIT",
+            "type": "make",
+            "build": {
+                "make_args": ["GREETING=compiled"],
+                "targets": ["hello"],
+                "binaries": ["hello"]
+            }
+        }"#,
+    )?;
+    create_source_archive(
+        base,
+        "hello",
+        &[
+            (
+                "hello.c",
+                "#include <stdio.h>\nint main(void) { puts(GREETING); return 0; }\n",
+            ),
+            (
+                "Makefile",
+                "hello: hello.c\n\t$(CC) $(CFLAGS) -DGREETING='\"$(GREETING)\"' -o hello hello.c $(LDFLAGS)\n",
+            ),
+        ],
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("compile")
+        .arg("--native")
+        .arg("--dir")
+        .arg(base)
+        .arg("--base")
+        .arg(base)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==> Compiling package: hello"))
+        .stdout(predicate::str::contains("-> Collecting hello"));
+
+    let archive = base.join(format!("binaries/hello.{}.tar.gz", os_type));
+    assert!(archive.exists());
+    let files = cbp::list_archive_files(&archive)?;
+    assert!(files.contains("bin/hello"));
+
+    // Packages without a build system are rejected
+    std::fs::write(
+        base.join("packages/hello.json"),
+        r#"{
+            "name": "hello",
+            "version": "1.0",
+            "description": "Hello world",
+            "homepage": "https://example.com",
+            "license": "MIT",
+            "type": "prebuild"
+        }"#,
+    )?;
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("compile")
+        .arg("--native")
+        .arg("--dir")
+        .arg(base)
+        .arg("--base")
+        .arg(base)
+        .arg("hello")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be compiled"));
+
+    Ok(())
+}
+
+/// Write an executable script into `dir`
+#[cfg(unix)]
+fn write_script(dir: &std::path::Path, name: &str, script: &str) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(name);
+    std::fs::write(&path, script)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+/// A `zig` standing in for the cross toolchain, logging its command lines
+/// to `$CBP_TEST_LOG` and compiling with the native tools
+const FAKE_ZIG: &str = r#"#!/bin/sh
+echo "zig $*" >> "$CBP_TEST_LOG"
+tool=$1
+shift
+if [ "$1" = "-target" ]; then
+    shift 2
+fi
+case $tool in
+    cc) exec cc "$@" ;;
+    c++) exec c++ "$@" ;;
+    *) exec "$tool" "$@" ;;
+esac
+"#;
+
+/// A `cmake` that logs its command lines to `$CBP_TEST_LOG` and builds
+/// `hello.c` with `$CC` as a real project would
+const FAKE_CMAKE: &str = r#"#!/bin/sh
+echo "cmake $*" >> "$CBP_TEST_LOG"
+case $1 in
+    -S)
+        for arg; do
+            case $arg in
+                -DCMAKE_INSTALL_PREFIX=*) echo "${arg#*=}" > prefix.txt ;;
+            esac
+        done
+        ;;
+    --build) $CC $CFLAGS -o hello hello.c ;;
+    --install)
+        prefix=$(cat prefix.txt)
+        mkdir -p "$prefix/bin"
+        cp hello "$prefix/bin/"
+        ;;
+esac
+"#;
+
+const HELLO_C: &str =
+    "#include <stdio.h>\nint main(void) { puts(\"hello\"); return 0; }\n";
+
+/// Write `packages/{pkg}.json` of a compiled package
+fn write_compile_package(
+    base: &std::path::Path,
+    pkg: &str,
+    pkg_type: &str,
+    build: serde_json::Value,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(base.join("packages"))?;
+    let json = serde_json::json!({
+        "name": pkg,
+        "version": "1.0",
+        "description": "Hello world",
+        "homepage": "https://example.com",
+        "license": "MIT",
+        "type": pkg_type,
+        "build": build
+    });
+    std::fs::write(
+        base.join(format!("packages/{}.json", pkg)),
+        serde_json::to_string_pretty(&json)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn command_build_compile_toolchain() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path().join("base");
+    let fake_bin = temp_dir.path().join("fake");
+    let log = temp_dir.path().join("commands.log");
+    write_script(&fake_bin, "zig", FAKE_ZIG)?;
+    let path = format!("{}:{}", fake_bin.display(), std::env::var("PATH")?);
+
+    write_compile_package(&base, "hello", "make", serde_json::json!({}))?;
+    create_source_archive(
+        &base,
+        "hello",
+        &[
+            ("hello.c", HELLO_C),
+            (
+                "Makefile",
+                "hello: hello.c\n\t$(CC) $(CFLAGS) -o hello hello.c $(LDFLAGS)\n",
+            ),
+        ],
+    )?;
+
+    // Without --native, zig cross-compiles for the package type
+    Command::cargo_bin("cbp")?
+        .env("PATH", &path)
+        .env("CBP_TEST_LOG", &log)
+        .arg("build")
+        .arg("compile")
+        .arg("--dir")
+        .arg(&base)
+        .arg("--base")
+        .arg(&base)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("-> Collecting hello"));
+
+    let commands = std::fs::read_to_string(&log)?;
+    assert!(commands.contains("zig cc -target x86_64-linux-gnu.2.17"));
+    assert!(commands.contains(&format!("-I{} -O3", base.join("include").display())));
+    assert!(commands.contains(&format!("-L{}", base.join("lib").display())));
+    assert!(base.join("binaries/hello.linux.tar.gz").exists());
+
+    // CFLAGS from the package env replace the default ones
+    std::fs::remove_file(&log)?;
+    write_compile_package(
+        &base,
+        "hello",
+        "make",
+        serde_json::json!({ "env": { "CFLAGS": "-DCUSTOM" } }),
+    )?;
+    Command::cargo_bin("cbp")?
+        .env("PATH", &path)
+        .env("CBP_TEST_LOG", &log)
+        .arg("build")
+        .arg("compile")
+        .arg("--dir")
+        .arg(&base)
+        .arg("--base")
+        .arg(&base)
+        .arg("hello")
+        .assert()
+        .success();
+
+    let commands = std::fs::read_to_string(&log)?;
+    assert!(commands.contains("-DCUSTOM"));
+    assert!(!commands.contains("-O3"));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn command_build_compile_rust() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path();
+
+    write_compile_package(base, "hello", "rust", serde_json::json!({}))?;
+    create_source_archive(
+        base,
+        "hello",
+        &[
+            (
+                "Cargo.toml",
+                "[package]\nname = \"hello\"\nversion = \"1.0.0\"\nedition = \"2021\"\n",
+            ),
+            ("src/main.rs", "fn main() {\n    println!(\"hello\");\n}\n"),
+        ],
+    )?;
+
+    // Binaries come from `cargo metadata` when none are listed
+    Command::cargo_bin("cbp")?
+        .env_remove("CARGO_TARGET_DIR")
+        .env("CARGO_NET_OFFLINE", "true")
+        .arg("build")
+        .arg("compile")
+        .arg("--native")
+        .arg("--dir")
+        .arg(base)
+        .arg("--base")
+        .arg(base)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "-> Running: cargo build --release",
+        ))
+        .stdout(predicate::str::contains("-> Collecting hello"));
+
+    let archive = base.join(format!("binaries/hello.{}.tar.gz", cbp::get_os_type()?));
+    let files = cbp::list_archive_files(&archive)?;
+    assert!(files.contains("bin/hello"));
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn command_build_compile_cmake() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path().join("base");
+    let fake_bin = temp_dir.path().join("fake");
+    let log = temp_dir.path().join("commands.log");
+    write_script(&fake_bin, "cmake", FAKE_CMAKE)?;
+    write_script(&fake_bin, "zig", FAKE_ZIG)?;
+    let path = format!("{}:{}", fake_bin.display(), std::env::var("PATH")?);
+
+    write_compile_package(
+        &base,
+        "hello",
+        "cmake",
+        serde_json::json!({ "configure": ["-DBUILD_TESTING=OFF"] }),
+    )?;
+    create_source_archive(
+        &base,
+        "hello",
+        &[
+            ("hello.c", HELLO_C),
+            ("CMakeLists.txt", "project(hello C)\n"),
+        ],
+    )?;
+
+    // Natively, everything installed into the prefix is packed
+    Command::cargo_bin("cbp")?
+        .env("PATH", &path)
+        .env("CBP_TEST_LOG", &log)
+        .arg("build")
+        .arg("compile")
+        .arg("--native")
+        .arg("--dir")
+        .arg(&base)
+        .arg("--base")
+        .arg(&base)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("-> Creating tarball from"));
+
+    let commands = std::fs::read_to_string(&log)?;
+    assert!(commands.contains("-DCMAKE_BUILD_TYPE=Release"));
+    assert!(commands.contains("-DCMAKE_INSTALL_PREFIX="));
+    assert!(commands.contains("-DBUILD_TESTING=OFF"));
+    assert!(commands.contains("cmake --install build"));
+    assert!(!commands.contains("zig"));
+    let archive = base.join("binaries/hello.linux.tar.gz");
+    let files = cbp::list_archive_files(&archive)?;
+    assert!(files.contains("bin/hello"));
+
+    // Cross builds hand the zig target to CMake
+    std::fs::remove_file(&log)?;
+    Command::cargo_bin("cbp")?
+        .env("PATH", &path)
+        .env("CBP_TEST_LOG", &log)
+        .arg("build")
+        .arg("compile")
+        .arg("--dir")
+        .arg(&base)
+        .arg("--base")
+        .arg(&base)
+        .arg("hello")
+        .assert()
+        .success();
+
+    let commands = std::fs::read_to_string(&log)?;
+    assert!(commands.contains("-DCMAKE_C_COMPILER_TARGET=x86_64-linux-gnu.2.17"));
+    assert!(commands.contains("zig cc"));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn command_build_compile_autotools() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path();
+
+    write_compile_package(base, "hello", "autotools", serde_json::json!({}))?;
+    // A hand-written configure taking the toolchain from the environment
+    create_source_archive(
+        base,
+        "hello",
+        &[
+            ("hello.c", HELLO_C),
+            (
+                "configure",
+                r#"#!/bin/sh
+for arg; do
+    case $arg in
+        --prefix=*) prefix=${arg#*=} ;;
+    esac
+done
+printf 'hello: hello.c\n\t%s %s -o hello hello.c %s\n' "$CC" "$CFLAGS" "$LDFLAGS" > Makefile
+printf 'install: hello\n\tmkdir -p %s/bin %s/lib/pkgconfig\n' "$prefix" "$prefix" >> Makefile
+printf '\tcp hello %s/bin/\n' "$prefix" >> Makefile
+printf '\techo prefix=%s > %s/lib/pkgconfig/hello.pc\n' "$prefix" "$prefix" >> Makefile
+"#,
+            ),
+        ],
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("compile")
+        .arg("--native")
+        .arg("--dir")
+        .arg(base)
+        .arg("--base")
+        .arg(base)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "-> Running: ./configure --prefix=",
+        ))
+        .stdout(predicate::str::contains(
+            "-> Relocated lib/pkgconfig/hello.pc",
+        ));
+
+    let archive = base.join(format!("binaries/hello.{}.tar.gz", cbp::get_os_type()?));
+    let files = cbp::list_archive_files(&archive)?;
+    assert!(files.contains("bin/hello"));
+    assert!(files.contains("lib/pkgconfig/hello.pc"));
+
+    Ok(())
+}
+
+/// Run git with a fixed identity, failing on errors
+fn git(dir: &std::path::Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "protocol.file.allow=always",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "cbp")
+        .env("GIT_AUTHOR_EMAIL", "cbp@example.com")
+        .env("GIT_COMMITTER_NAME", "cbp")
+        .env("GIT_COMMITTER_EMAIL", "cbp@example.com")
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "git {:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[test]
+fn command_build_source_git() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path().join("base");
+    let repos = temp_dir.path().join("repos");
+
+    // A library used as a submodule
+    let lib = repos.join("lib");
+    std::fs::create_dir_all(&lib)?;
+    git(&lib, &["init", "--quiet"])?;
+    std::fs::write(lib.join("lib.c"), "int lib(void) { return 0; }\n")?;
+    git(&lib, &["add", "."])?;
+    git(&lib, &["commit", "--quiet", "-m", "lib"])?;
+
+    // The package repository, tagged, then moved on
+    let work = repos.join("hello");
+    std::fs::create_dir_all(&work)?;
+    git(&work, &["init", "--quiet"])?;
+    std::fs::write(work.join("hello.c"), "int main(void) { return 0; }\n")?;
+    git(
+        &work,
+        &[
+            "submodule",
+            "add",
+            "--quiet",
+            lib.to_str().unwrap(),
+            "vendor/lib",
+        ],
+    )?;
+    git(&work, &["add", "."])?;
+    git(&work, &["commit", "--quiet", "-m", "v1.0"])?;
+    git(&work, &["tag", "v1.0"])?;
+    let commit = git(&work, &["rev-parse", "HEAD"])?;
+    std::fs::write(work.join("NEWS"), "unreleased\n")?;
+    git(&work, &["add", "."])?;
+    git(&work, &["commit", "--quiet", "-m", "next"])?;
+
+    git(
+        &repos,
+        &["clone", "--quiet", "--bare", "hello", "hello.git"],
+    )?;
+
+    std::fs::create_dir_all(base.join("packages"))?;
+    std::fs::write(
+        base.join("packages/hello.json"),
+        format!(
+            r#"{{
+  "name": "hello",
+  "version": "1.0",
+  "description": "Hello world",
+  "homepage": "https://example.com",
+  "license": "MIT",
+  "type": "make",
+  "downloads": {{
+    "source": {{
+      "git": "{}",
+      "rev": "v1.0"
+    }}
+  }}
+}}"#,
+            repos.join("hello.git").display()
+        ),
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(&base)
+        .arg("hello");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "-> Resolved commit {}",
+            commit
+        )));
+
+    let output_tar = base.join("sources/hello.tar.gz");
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("hello/hello.c"));
+    assert!(files.contains("hello/vendor/lib/lib.c")); // Submodule
+    assert!(files.contains("hello/.cbp-git"));
+    assert!(!files.contains("hello/NEWS")); // Pinned to the tag
+    assert!(!files.contains(".git/"));
+
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+        std::fs::File::open(&output_tar)?,
+    ));
+    let mut record = String::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.ends_with(".cbp-git") {
+            std::io::Read::read_to_string(&mut entry, &mut record)?;
+        }
+    }
+    assert!(record.contains(&format!("commit {}\n", commit)));
+
+    Ok(())
+}
+
+#[test]
+fn command_build_lint() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let collect_dir = temp_dir.path().join("collect");
+    std::fs::create_dir_all(collect_dir.join("bin"))?;
+    std::fs::copy("tests/jq-macos-arm64", collect_dir.join("bin/jq"))?;
+    std::fs::write(collect_dir.join("bin/jq.sh"), "#!/bin/sh\n")?;
+
+    let archive = temp_dir.path().join("jq.macos.tar.gz");
+    Command::cargo_bin("cbp")?
+        .arg("tar")
+        .arg(&collect_dir)
+        .arg("-o")
+        .arg(&archive)
+        .assert()
+        .success();
+
+    // Type from the archive name
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("lint")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("-> bin/jq: Mach-O arm64"))
+        .stdout(predicate::str::contains("1 binaries, 0 violations"));
+
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("lint")
+        .arg(&archive)
+        .arg("--type")
+        .arg("linux")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "error: Mach-O binary in a linux package",
+        ))
+        .stderr(predicate::str::contains("Lint failed"));
+
+    // Unknown type
+    let renamed = temp_dir.path().join("jq.tar.gz");
+    std::fs::copy(&archive, &renamed)?;
+    Command::cargo_bin("cbp")?
+        .arg("build")
+        .arg("lint")
+        .arg(&renamed)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --type"));
+
+    Ok(())
+}
@@ -141,7 +141,7 @@ fn command_collect_with_ignore() -> anyhow::Result<()> {
         .arg("--mode")
         .arg("files") // Explicitly specify files mode
         .arg("--ignore")
-        .arg(".txt")
+        .arg("*.txt")
         .arg("-o")
         .arg(&output_tar)
         .current_dir(temp_dir.path())
@@ -249,9 +249,349 @@ fn command_collect_vcpkg_mode() -> anyhow::Result<()> {
     assert!(files.contains("bin/bzip2recover")); // tools moved to bin/
     assert!(files.contains("lib/libbz2.a")); // libraries in lib/
     assert!(files.contains("include/bzlib.h")); // headers in include/
+    assert!(files.contains("share/licenses/bzip2/copyright")); // port copyright
 
     assert!(!files.contains("tools/bzip2/bzip2"));
     assert!(!files.contains("tools/bzip2/bzip2recover"));
 
+    // Patterns leave out the triplet, as vcpkg tools move to bin/
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--mode")
+        .arg("vcpkg")
+        .arg("--ignore")
+        .arg("tools/bzip2/bzip2rec*")
+        .arg("--ignore")
+        .arg("lib/pkgconfig")
+        .arg("-o")
+        .arg("ignored.tar.gz")
+        .current_dir(temp_dir.path())
+        .arg(&list_file)
+        .assert()
+        .success();
+    let files = cbp::list_archive_files(&temp_dir.path().join("ignored.tar.gz"))?;
+    assert!(files.contains("bin/bzip2\n"));
+    assert!(!files.contains("bin/bzip2recover"));
+    assert!(!files.contains("lib/pkgconfig/bzip2.pc"));
+    assert!(files.contains("lib/libbz2.a"));
+
+    Ok(())
+}
+
+#[test]
+fn command_collect_compression() -> anyhow::Result<()> {
+    let temp_dir = setup_test_files()?;
+
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--compression")
+        .arg("xz")
+        .current_dir(temp_dir.path())
+        .arg("test.txt")
+        .assert()
+        .success();
+
+    let output_tar = temp_dir.path().join("test.tar.xz");
+    assert_eq!(
+        cbp::CompressionFormat::detect(&output_tar)?,
+        Some(cbp::CompressionFormat::Xz)
+    );
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("test.txt"));
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn command_collect_split_debug() -> anyhow::Result<()> {
+    let has_tool = |tool: &str| {
+        std::process::Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|o| o.status.success())
+    };
+    if !has_tool("cc") || !has_tool("objcopy") {
+        return Ok(());
+    }
+
+    let temp_dir = tempfile::tempdir()?;
+    let src = temp_dir.path().join("hello.c");
+    std::fs::write(&src, "int main(void) { return 0; }\n")?;
+    let status = std::process::Command::new("cc")
+        .args(["-g", "-o", "hello", "hello.c"])
+        .current_dir(temp_dir.path())
+        .status()?;
+    assert!(status.success());
+
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--mode")
+        .arg("bin")
+        .arg("--split-debug")
+        .arg("-o")
+        .arg("hello.linux.tar.gz")
+        .arg("hello")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Stripped bin/hello"));
+
+    let files = cbp::list_archive_files(&temp_dir.path().join("hello.linux.tar.gz"))?;
+    assert!(files.contains("bin/hello"));
+    assert!(!files.contains("bin/hello.debug"));
+
+    let debug_tar = temp_dir.path().join("hello.linux.debug.tar.gz");
+    let files = cbp::list_archive_files(&debug_tar)?;
+    assert!(files.contains("bin/hello.debug"));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn command_collect_symlinks() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let lib = temp_dir.path().join("lib");
+    fs::create_dir(&lib)?;
+    fs::write(lib.join("libfoo.so.1.2"), "foo")?;
+    std::os::unix::fs::symlink("libfoo.so.1.2", lib.join("libfoo.so.1"))?;
+    std::os::unix::fs::symlink("libfoo.so.1", lib.join("libfoo.so"))?;
+    // Points outside of the package, collected as a copy
+    fs::write(temp_dir.path().join("outside.txt"), "outside")?;
+    std::os::unix::fs::symlink("../outside.txt", lib.join("outside.txt"))?;
+
+    let output_tar = temp_dir.path().join("output.tar.gz");
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("lib")
+        .arg("--copy")
+        .arg("libfoo.so.1.2=libfoo.so.1.2.0")
+        .arg("--symlink")
+        .arg("-o")
+        .arg(&output_tar)
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    let mut links = std::collections::BTreeMap::new();
+    let mut regular = vec![];
+    let mut archive = cbp::open_archive(&output_tar)?;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        match entry.header().entry_type() {
+            tar::EntryType::Symlink => {
+                let target = entry.link_name()?.unwrap().to_string_lossy().to_string();
+                links.insert(path, target);
+            }
+            tar::EntryType::Regular => regular.push(path),
+            _ => {}
+        }
+    }
+
+    assert_eq!(links["lib/libfoo.so"], "libfoo.so.1");
+    assert_eq!(links["lib/libfoo.so.1"], "libfoo.so.1.2");
+    assert_eq!(links["lib/libfoo.so.1.2.0"], "libfoo.so.1.2");
+    assert!(regular.contains(&"lib/libfoo.so.1.2".to_string()));
+    assert!(regular.contains(&"lib/outside.txt".to_string()));
+    Ok(())
+}
+
+#[test]
+fn command_collect_ignore_include() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    for path in [
+        "test/a.h",
+        "latest/b.h",
+        "include/contest.h",
+        "include/c.txt",
+    ] {
+        let path = temp_dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, "")?;
+    }
+
+    let output_tar = temp_dir.path().join("output.tar.gz");
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--ignore")
+        .arg("test")
+        .arg("--include")
+        .arg("*.h")
+        .arg("-o")
+        .arg(&output_tar)
+        .arg("test")
+        .arg("latest")
+        .arg("include")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(!files.contains("test/a.h"));
+    assert!(files.contains("latest/b.h"));
+    assert!(files.contains("include/contest.h"));
+    assert!(!files.contains("include/c.txt"));
+    Ok(())
+}
+
+#[test]
+fn command_collect_package_layout() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    for path in [
+        "build/tool",
+        "build/libtool.a",
+        "src/tool.h",
+        "doc/man/tool.1",
+    ] {
+        let path = temp_dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, "")?;
+    }
+    fs::write(
+        temp_dir.path().join("tool.json"),
+        r#"{
+  "name": "tool",
+  "version": "1.0",
+  "description": "A tool",
+  "homepage": "https://example.com",
+  "license": "MIT",
+  "type": "make",
+  "collect": {
+    "bin": ["build/tool"],
+    "lib": ["build/libtool*"],
+    "include": ["src/*.h"],
+    "share": ["doc/man"],
+    "ignore": ["re:\\.bak$"]
+  }
+}"#,
+    )?;
+    fs::write(temp_dir.path().join("build/libtool.a.bak"), "")?;
+
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--package")
+        .arg("tool.json")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    let files = cbp::list_archive_files(&temp_dir.path().join("tool.tar.gz"))?;
+    assert!(files.contains("bin/tool"));
+    assert!(files.contains("lib/libtool.a"));
+    assert!(files.contains("include/tool.h"));
+    assert!(files.contains("share/man/tool.1"));
+    assert!(!files.contains("lib/libtool.a.bak"));
+    Ok(())
+}
+
+#[test]
+fn command_collect_relocate_prefix() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let installed = temp_dir.path().canonicalize()?.join("installed");
+    let triplet = installed.join("x64-linux");
+    let pc = format!(
+        "prefix={}\nincludedir=${{prefix}}/include\nLibs: -L{}/lib -lhts\n",
+        triplet.display(),
+        triplet.display()
+    );
+    fs::create_dir_all(triplet.join("lib/pkgconfig"))?;
+    fs::write(triplet.join("lib/pkgconfig/hts.pc"), pc)?;
+    fs::write(triplet.join("lib/libhts.a"), "")?;
+    fs::create_dir_all(installed.join("vcpkg/info"))?;
+    let list_file = installed.join("vcpkg/info/hts_1.0_x64-linux.list");
+    fs::write(
+        &list_file,
+        "x64-linux/lib/libhts.a\nx64-linux/lib/pkgconfig/hts.pc\n",
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--mode")
+        .arg("vcpkg")
+        .arg(&list_file)
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Relocated lib/pkgconfig/hts.pc"));
+
+    let output_tar = temp_dir.path().join("hts.linux.tar.gz");
+    let content = cbp::read_file_from_archive(&output_tar, "lib/pkgconfig/hts.pc")?;
+    assert!(content.starts_with("prefix=@@CBP_PREFIX@@\n"));
+    assert!(content.contains("-L@@CBP_PREFIX@@/lib"));
+
+    // The placeholder becomes the cbp home on install
+    let cbp_home = temp_dir.path().join("home");
+    fs::create_dir_all(cbp_home.join("cache"))?;
+    let os_type = cbp::get_os_type()?;
+    fs::copy(
+        &output_tar,
+        cbp_home
+            .join("cache")
+            .join(format!("hts.{}.tar.gz", os_type)),
+    )?;
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("hts")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(cbp_home.join("lib/pkgconfig/hts.pc"))?;
+    assert!(!content.contains("@@CBP_PREFIX@@"));
+    assert!(content.contains("/home/lib -lhts"));
+    Ok(())
+}
+
+#[test]
+fn command_collect_licenses() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let src_dir = temp_dir.path().join("tool-1.0");
+    fs::create_dir_all(&src_dir)?;
+    fs::write(src_dir.join("LICENSE"), "MIT License\n")?;
+    fs::write(src_dir.join("COPYING.LIB"), "LGPL\n")?;
+    fs::write(src_dir.join("tool"), "#!/bin/sh\n")?;
+
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--mode")
+        .arg("bin")
+        .arg("-o")
+        .arg("tool3.11.linux.tar.gz")
+        .arg("--licenses")
+        .arg("tool-1.0")
+        .arg("tool-1.0/tool")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "License share/licenses/tool3.11/LICENSE",
+        ));
+
+    let output_tar = temp_dir.path().join("tool3.11.linux.tar.gz");
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("bin/tool"));
+    assert!(files.contains("share/licenses/tool3.11/LICENSE"));
+    assert!(files.contains("share/licenses/tool3.11/COPYING.LIB"));
+
+    // The current directory is not searched without --licenses
+    Command::cargo_bin("cbp")?
+        .arg("collect")
+        .arg("--mode")
+        .arg("bin")
+        .arg("-o")
+        .arg("tool.linux.tar.gz")
+        .arg("--metadata")
+        .arg(r#"{"name":"tool","version":"1.0"}"#)
+        .arg("tool-1.0/tool")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    let output_tar = temp_dir.path().join("tool.linux.tar.gz");
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("bin/tool"));
+    assert!(!files.contains("share/licenses"));
     Ok(())
 }
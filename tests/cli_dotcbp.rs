@@ -102,6 +102,7 @@ fn command_list_specific_package() -> anyhow::Result<()> {
 #[test]
 fn command_check() -> anyhow::Result<()> {
     let temp_dir = setup_test_data()?;
+    std::fs::write(temp_dir.path().join("mirrors.json"), "{}")?;
     let mut cmd = Command::cargo_bin("cbp")?;
     let output = cmd
         .arg("check")
@@ -113,6 +114,7 @@ fn command_check() -> anyhow::Result<()> {
 
     assert!(stdout.lines().count() > 0);
     assert!(stdout.contains("==> Unmanaged files"));
+    assert!(!stdout.contains("mirrors.json"));
 
     Ok(())
 }
@@ -126,7 +128,7 @@ fn command_remove() -> anyhow::Result<()> {
     Command::cargo_bin("cbp")?
         .arg("remove")
         .arg("--dir")
-        .arg(&dest_dir)
+        .arg(dest_dir)
         .arg("nonexistent")
         .assert()
         .success()
@@ -134,11 +136,25 @@ fn command_remove() -> anyhow::Result<()> {
             "==> Package nonexistent is not installed",
         ));
 
+    // Test previewing removal
+    Command::cargo_bin("cbp")?
+        .arg("remove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .arg("--dry-run")
+        .arg("zlib")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==> Would remove zlib"))
+        .stdout(predicate::str::contains("Would remove: lib/libz.a"));
+    assert!(dest_dir.join("records/zlib.files").exists());
+    assert!(dest_dir.join("lib/libz.a").exists());
+
     // Test removing existing package
     Command::cargo_bin("cbp")?
         .arg("remove")
         .arg("--dir")
-        .arg(&dest_dir)
+        .arg(dest_dir)
         .arg("zlib")
         .assert()
         .success()
@@ -153,6 +169,10 @@ fn command_remove() -> anyhow::Result<()> {
     assert!(dest_dir.join("records/bzip2.files").exists());
     assert!(dest_dir.join("lib/libbz2.a").exists());
 
+    // Verify empty directories are pruned
+    assert!(!dest_dir.join("lib/pkgconfig").exists());
+    assert!(dest_dir.join("lib").exists());
+
     Ok(())
 }
 
@@ -187,3 +207,461 @@ fn command_local() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+fn create_script_package(path: &std::path::Path, name: &str) -> anyhow::Result<()> {
+    let script = format!("#!/bin/sh\necho \"{} $@\"\nexit 3\n", name);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(script.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+
+    let file = std::fs::File::create(path)?;
+    let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+    let mut archive = tar::Builder::new(gz);
+    archive.append_data(&mut header, format!("bin/{}", name), script.as_bytes())?;
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn command_run() -> anyhow::Result<()> {
+    let temp = tempfile::TempDir::new()?;
+    let cbp_home = temp.path();
+    std::fs::create_dir_all(cbp_home.join("cache"))?;
+    let os_type = cbp::get_os_type()?;
+    create_script_package(
+        &cbp_home
+            .join("cache")
+            .join(format!("hello.{}.tar.gz", os_type)),
+        "hello",
+    )?;
+
+    // Run from cache without installing
+    Command::cargo_bin("cbp")?
+        .arg("run")
+        .arg("--dir")
+        .arg(cbp_home)
+        .arg("hello")
+        .arg("--")
+        .arg("--world")
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("hello --world"));
+
+    assert!(!cbp_home.join("records/hello.files").exists());
+    assert!(!cbp_home.join("bin/hello").exists());
+
+    // Missing binary in package
+    Command::cargo_bin("cbp")?
+        .arg("exec")
+        .arg("--dir")
+        .arg(cbp_home)
+        .arg("--bin")
+        .arg("nonexistent")
+        .arg("hello")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Binary nonexistent not found"));
+
+    // Run the installed package
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg(cbp_home)
+        .arg("hello")
+        .current_dir(cbp_home)
+        .assert()
+        .success();
+    std::fs::remove_file(
+        cbp_home
+            .join("cache")
+            .join(format!("hello.{}.tar.gz", os_type)),
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("run")
+        .arg("--dir")
+        .arg(cbp_home)
+        .arg("hello")
+        .arg("installed")
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("hello installed"));
+
+    Ok(())
+}
+
+#[test]
+fn command_remove_dependencies() -> anyhow::Result<()> {
+    let temp_dir = setup_test_data()?;
+    let dest_dir = temp_dir.path();
+
+    // bzip2 was installed as a dependency of zlib
+    std::fs::write(
+        dest_dir.join("records/zlib.json"),
+        r#"{"name": "zlib", "explicit": true, "dependencies": ["bzip2"]}"#,
+    )?;
+    std::fs::write(
+        dest_dir.join("records/bzip2.json"),
+        r#"{"name": "bzip2", "explicit": false}"#,
+    )?;
+
+    // Nothing to clean while zlib is installed
+    Command::cargo_bin("cbp")?
+        .arg("autoremove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No unused dependencies found"));
+
+    // Refuse to remove a dependency
+    Command::cargo_bin("cbp")?
+        .arg("remove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .arg("bzip2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "==> Package bzip2 is required by: zlib",
+        ));
+    assert!(dest_dir.join("records/bzip2.files").exists());
+
+    // Recursive removal takes the unused dependency along
+    Command::cargo_bin("cbp")?
+        .arg("remove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .arg("--recursive")
+        .arg("--dry-run")
+        .arg("zlib")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==> Would remove zlib"))
+        .stdout(predicate::str::contains("==> Would remove bzip2"));
+
+    // Orphans are cleaned by autoremove
+    Command::cargo_bin("cbp")?
+        .arg("remove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .arg("zlib")
+        .assert()
+        .success();
+    assert!(!dest_dir.join("records/zlib.json").exists());
+
+    Command::cargo_bin("cbp")?
+        .arg("autoremove")
+        .arg("--dir")
+        .arg(dest_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==> Removing bzip2"));
+    assert!(!dest_dir.join("records/bzip2.files").exists());
+    assert!(!dest_dir.join("lib/libbz2.a").exists());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn command_doctor() -> anyhow::Result<()> {
+    let temp_dir = setup_test_data()?;
+    let cbp_home = temp_dir.path();
+    let user_home = tempfile::TempDir::new()?;
+
+    // Broken installation: foreign cbp binary, leftovers, no PATH or rc block
+    std::fs::write(cbp_home.join("cache/zlib.linux.tar.gz.incomplete"), "")?;
+    std::fs::remove_file(cbp_home.join("include/zlib.h"))?;
+    std::os::unix::fs::symlink("libfoo.so.1", cbp_home.join("lib/libfoo.so"))?;
+
+    Command::cargo_bin("cbp")?
+        .arg("doctor")
+        .arg("--dir")
+        .arg(cbp_home)
+        .env("HOME", user_home.path())
+        .env("PATH", "/usr/bin:/bin")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("[FAIL] bin directory is on PATH"))
+        .stdout(predicate::str::contains("zlib: 1 missing files"))
+        .stdout(predicate::str::contains(
+            "cache/zlib.linux.tar.gz.incomplete",
+        ))
+        .stdout(predicate::str::contains(
+            "lib/libfoo.so is a broken symlink",
+        ))
+        .stdout(predicate::str::contains("Fix: run `cbp init"));
+
+    // Healthy installation
+    let healthy = tempfile::TempDir::new()?;
+    let cbp_home = healthy.path().join(".cbp");
+    std::fs::create_dir_all(cbp_home.join("bin"))?;
+    std::fs::copy(
+        assert_cmd::cargo::cargo_bin("cbp"),
+        cbp_home.join("bin/cbp"),
+    )?;
+    std::fs::write(
+        healthy.path().join(".bashrc"),
+        "# .cbp start\nexport PATH=\"$HOME/.cbp/bin:$PATH\"\n# .cbp end\n",
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("doctor")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .env("HOME", healthy.path())
+        .env(
+            "PATH",
+            format!("{}:/usr/bin:/bin", cbp_home.join("bin").display()),
+        )
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[OK]   installed cbp matches"))
+        .stdout(predicate::str::contains("==> All checks passed"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn command_doctor_libs() -> anyhow::Result<()> {
+    let has_cc = std::process::Command::new("cc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !has_cc {
+        return Ok(());
+    }
+
+    // libfoo.so needs libbar.so.1
+    let build_dir = tempfile::TempDir::new()?;
+    std::fs::write(
+        build_dir.path().join("bar.c"),
+        "int bar(void) { return 1; }\n",
+    )?;
+    std::fs::write(
+        build_dir.path().join("foo.c"),
+        "int bar(void);\nint foo(void) { return bar(); }\n",
+    )?;
+    for args in [
+        "-shared -fPIC -Wl,-soname,libbar.so.1 -o libbar.so bar.c",
+        "-shared -fPIC -o libfoo.so foo.c -L. -lbar",
+    ] {
+        let status = std::process::Command::new("cc")
+            .args(args.split(' '))
+            .current_dir(build_dir.path())
+            .status()?;
+        assert!(status.success());
+    }
+
+    let cbp_home = tempfile::TempDir::new()?;
+    std::fs::create_dir_all(cbp_home.path().join("lib"))?;
+    std::fs::copy(
+        build_dir.path().join("libfoo.so"),
+        cbp_home.path().join("lib/libfoo.so"),
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("doctor")
+        .arg("--dir")
+        .arg(cbp_home.path())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "lib/libfoo.so needs libbar.so.1, which is not found",
+        ))
+        .stdout(predicate::str::contains("needs libc.so.6").not());
+
+    // Resolved once the needed library is installed
+    std::fs::copy(
+        build_dir.path().join("libbar.so"),
+        cbp_home.path().join("lib/libbar.so.1"),
+    )?;
+    Command::cargo_bin("cbp")?
+        .arg("doctor")
+        .arg("--dir")
+        .arg(cbp_home.path())
+        .assert()
+        .stdout(predicate::str::contains("[OK]   dynamic libraries resolve"));
+
+    Ok(())
+}
+
+#[test]
+fn command_local_prefix_placeholder() -> anyhow::Result<()> {
+    let temp = tempfile::TempDir::new()?;
+    let pkg_dir = temp.path().join("pkg");
+    std::fs::create_dir_all(pkg_dir.join("bin"))?;
+    std::fs::write(
+        pkg_dir.join("bin/demo"),
+        "#!/bin/sh\nexec java -jar @@CBP_PREFIX@@/libexec/demo.jar \"$@\"\n",
+    )?;
+
+    // Install under a custom --dir
+    let cbp_home = temp.path().join("custom");
+    std::fs::create_dir_all(cbp_home.join("cache"))?;
+    let os_type = cbp::get_os_type()?;
+    let pkg_file = cbp_home
+        .join("cache")
+        .join(format!("demo.{}.tar.gz", os_type));
+    Command::cargo_bin("cbp")?
+        .arg("tar")
+        .arg(&pkg_dir)
+        .arg("-o")
+        .arg(&pkg_file)
+        .assert()
+        .success();
+
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("demo")
+        .current_dir(temp.path())
+        .assert()
+        .success();
+
+    let content = std::fs::read_to_string(cbp_home.join("bin/demo"))?;
+    assert!(content.contains(&format!("-jar {}/libexec/demo.jar", cbp_home.display())));
+    Ok(())
+}
+
+#[test]
+fn command_local_prefix_placeholder_relative_dir() -> anyhow::Result<()> {
+    let temp = tempfile::TempDir::new()?;
+    let pkg_dir = temp.path().join("pkg");
+    std::fs::create_dir_all(pkg_dir.join("bin"))?;
+    std::fs::write(
+        pkg_dir.join("bin/demo"),
+        "#!/bin/sh\nexec @@CBP_PREFIX@@/libexec/demo \"$@\"\n",
+    )?;
+
+    let cbp_home = temp.path().join("myhome");
+    std::fs::create_dir_all(cbp_home.join("cache"))?;
+    let os_type = cbp::get_os_type()?;
+    let pkg_file = cbp_home
+        .join("cache")
+        .join(format!("demo.{}.tar.gz", os_type));
+    Command::cargo_bin("cbp")?
+        .arg("tar")
+        .arg(&pkg_dir)
+        .arg("-o")
+        .arg(&pkg_file)
+        .assert()
+        .success();
+
+    // A relative --dir still writes an absolute prefix
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg("myhome")
+        .arg("demo")
+        .current_dir(temp.path())
+        .assert()
+        .success();
+
+    let content = std::fs::read_to_string(cbp_home.join("bin/demo"))?;
+    let abs_home = dunce::canonicalize(&cbp_home)?;
+    assert!(content.contains(&format!("exec {}/libexec/demo", abs_home.display())));
+    assert!(!content.contains("exec myhome/"));
+    Ok(())
+}
+
+#[test]
+fn command_licenses() -> anyhow::Result<()> {
+    let temp = tempfile::TempDir::new()?;
+    let cbp_home = temp.path().join("home");
+    std::fs::create_dir_all(cbp_home.join("cache"))?;
+    let os_type = cbp::get_os_type()?;
+
+    // The package JSON of plain has a license, bare is unknown to the repository
+    let mut server = mockito::Server::new();
+    let _m1 = server
+        .mock("GET", "/wang-q/cbp/master/packages/plain.json")
+        .with_status(200)
+        .with_body(r#"{"name":"plain","license":"Apache-2.0"}"#)
+        .create();
+    let _m2 = server
+        .mock("GET", "/wang-q/cbp/master/packages/bare.json")
+        .with_status(404)
+        .create();
+
+    // One package with metadata and a license text, others without either
+    let pkg_dir = temp.path().join("demo");
+    std::fs::create_dir_all(pkg_dir.join("share/licenses/demo"))?;
+    std::fs::write(pkg_dir.join("share/licenses/demo/LICENSE"), "MIT\n")?;
+    let plain_dir = temp.path().join("plain");
+    std::fs::create_dir_all(plain_dir.join("bin"))?;
+    std::fs::write(plain_dir.join("bin/plain"), "#!/bin/sh\n")?;
+
+    for (pkg, dir, metadata) in [
+        (
+            "demo",
+            &pkg_dir,
+            Some(r#"{"name":"demo","version":"1.0","license":"MIT"}"#),
+        ),
+        ("plain", &plain_dir, None),
+        ("bare", &plain_dir, None),
+    ] {
+        let pkg_file = cbp_home
+            .join("cache")
+            .join(format!("{}.{}.tar.gz", pkg, os_type));
+        let mut cmd = Command::cargo_bin("cbp")?;
+        cmd.arg("tar").arg(dir).arg("-o").arg(&pkg_file);
+        if let Some(metadata) = metadata {
+            cmd.arg("--metadata").arg(metadata);
+        }
+        cmd.assert().success();
+
+        Command::cargo_bin("cbp")?
+            .arg("local")
+            .arg("--dir")
+            .arg(&cbp_home)
+            .arg(pkg)
+            .current_dir(temp.path())
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("cbp")?
+        .env("GITHUB_RAW_URL", server.url())
+        .arg("licenses")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("demo 1.0: MIT"))
+        .stdout(predicate::str::contains("share/licenses/demo/LICENSE"))
+        .stdout(predicate::str::contains("plain -: Apache-2.0"))
+        .stdout(predicate::str::contains("bare -: NOASSERTION"));
+
+    Command::cargo_bin("cbp")?
+        .env("GITHUB_RAW_URL", server.url())
+        .arg("licenses")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("--csv")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("name,version,license,files\n"))
+        .stdout(predicate::str::contains(
+            "bare,,NOASSERTION,\ndemo,1.0,MIT,share/licenses/demo/LICENSE\nplain,,Apache-2.0,\n",
+        ));
+
+    let output = Command::cargo_bin("cbp")?
+        .arg("licenses")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("--json")
+        .arg("demo")
+        .output()?;
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json[0]["license"], "MIT");
+    assert_eq!(json[0]["files"][0], "share/licenses/demo/LICENSE");
+    Ok(())
+}
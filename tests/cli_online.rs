@@ -27,7 +27,7 @@ fn command_avail() -> anyhow::Result<()> {
 
     // Override GitHub API URL with environment variable
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_API_URL", &server.url())
+    cmd.env("GITHUB_API_URL", server.url())
         .arg("avail")
         .arg("macos");
 
@@ -82,7 +82,7 @@ fn command_install() -> anyhow::Result<()> {
 
     // Override GitHub URL with environment variable
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RELEASE_URL", &server.url())
+    cmd.env("GITHUB_RELEASE_URL", server.url())
         .arg("install")
         .arg("--dir")
         .arg(temp_dir.path())
@@ -101,6 +101,217 @@ fn command_install() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn command_install_deps() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let os_type = cbp::get_os_type()?;
+
+    // Create mock server
+    let mut server = mockito::Server::new();
+
+    // Prepare test package data, the same archive serves both packages
+    let test_package = include_bytes!("zlib.macos.tar.gz");
+
+    let _m1 = server
+        .mock("GET", "/wang-q/cbp/master/packages/app.json")
+        .with_status(200)
+        .with_body(r#"{"name": "app", "dependencies": ["zlib"]}"#)
+        .create();
+    let _m2 = server
+        .mock("GET", "/wang-q/cbp/master/packages/zlib.json")
+        .with_status(200)
+        .with_body(r#"{"name": "zlib"}"#)
+        .create();
+    let _m3 = server
+        .mock(
+            "GET",
+            format!(
+                "/wang-q/cbp/releases/download/Binaries/app.{}.tar.gz",
+                os_type
+            )
+            .as_str(),
+        )
+        .with_status(200)
+        .with_body(test_package)
+        .create();
+    let _m4 = server
+        .mock(
+            "GET",
+            format!(
+                "/wang-q/cbp/releases/download/Binaries/zlib.{}.tar.gz",
+                os_type
+            )
+            .as_str(),
+        )
+        .with_status(200)
+        .with_body(test_package)
+        .create();
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .env("GITHUB_RAW_URL", server.url())
+        .arg("install")
+        .arg("--dir")
+        .arg(temp_dir.path())
+        .arg("--deps")
+        .arg("app");
+
+    let output = cmd.output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(output.status.success());
+
+    // Dependencies are installed first
+    let zlib_pos = stdout.find("==> Downloading zlib").unwrap();
+    let app_pos = stdout.find("==> Downloading app").unwrap();
+    assert!(zlib_pos < app_pos);
+
+    let dirs = cbp::CbpDirs::from(temp_dir.path().to_path_buf())?;
+    let app = dirs.read_record("app")?;
+    assert!(app.explicit);
+    assert_eq!(app.dependencies, vec!["zlib"]);
+    assert!(!dirs.read_record("zlib")?.explicit);
+
+    // Requesting a dependency explicitly promotes it
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("install")
+        .arg("--dir")
+        .arg(temp_dir.path())
+        .arg("zlib");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Marked as explicitly installed"));
+    assert!(dirs.read_record("zlib")?.explicit);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn command_run() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let os_type = cbp::get_os_type()?;
+
+    // Build a package containing a single script
+    let script = "#!/bin/sh\necho \"hello $@\"\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(script.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    archive.append_data(&mut header, "bin/hello", script.as_bytes())?;
+    let test_package = archive.into_inner()?.finish()?;
+
+    // Create mock server
+    let mut server = mockito::Server::new();
+    let _m = server
+        .mock(
+            "GET",
+            format!(
+                "/wang-q/cbp/releases/download/Binaries/hello.{}.tar.gz",
+                os_type
+            )
+            .as_str(),
+        )
+        .with_status(200)
+        .with_header("content-type", "application/gzip")
+        .with_body(test_package)
+        .create();
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("run")
+        .arg("--dir")
+        .arg(temp_dir.path())
+        .arg("hello")
+        .arg("--")
+        .arg("world");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("hello world"))
+        .stderr(predicate::str::contains("==> Downloading hello"));
+
+    // Package is cached but not installed
+    assert!(temp_dir
+        .path()
+        .join(format!("cache/hello.{}.tar.gz", os_type))
+        .exists());
+    assert!(!temp_dir.path().join("records/hello.files").exists());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn command_self_update() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let os_type = cbp::get_os_type()?;
+
+    // Run a copy of cbp so the test binary itself is not replaced
+    let exe = temp_dir.path().join("cbp");
+    std::fs::copy(assert_cmd::cargo::cargo_bin("cbp"), &exe)?;
+
+    let new_exe = "#!/bin/sh\necho \"cbp 99.0.0\"\n";
+    let digest = {
+        use sha2::Digest;
+        format!("{:x}", sha2::Sha256::digest(new_exe.as_bytes()))
+    };
+
+    // Create mock server
+    let mut server = mockito::Server::new();
+    let _m1 = server
+        .mock("GET", "/repos/wang-q/cbp/releases/latest")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"tag_name": "v99.0.0", "assets": [{{"name": "cbp.{}", "size": {}, "digest": "sha256:{}"}}]}}"#,
+            os_type,
+            new_exe.len(),
+            digest
+        ))
+        .create();
+    let _m2 = server
+        .mock(
+            "GET",
+            format!("/wang-q/cbp/releases/download/v99.0.0/cbp.{}", os_type).as_str(),
+        )
+        .with_status(200)
+        .with_body(new_exe)
+        .create();
+
+    // Check only
+    let mut cmd = Command::new(&exe);
+    cmd.env("GITHUB_API_URL", server.url())
+        .arg("self-update")
+        .arg("--check");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("==> Latest version: 99.0.0"))
+        .stdout(predicate::str::contains("cbp self-update"));
+
+    // Update
+    let mut cmd = Command::new(&exe);
+    cmd.env("GITHUB_API_URL", server.url())
+        .env("GITHUB_RELEASE_URL", server.url())
+        .arg("self-update");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("==> Updated"));
+
+    assert_eq!(std::fs::read_to_string(&exe)?, new_exe);
+    Command::new(&exe)
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout("cbp 99.0.0\n");
+
+    Ok(())
+}
+
 #[test]
 fn command_info() -> anyhow::Result<()> {
     // Create mock server
@@ -126,7 +337,7 @@ fn command_info() -> anyhow::Result<()> {
 
     // Test normal output
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RAW_URL", &server.url())
+    cmd.env("GITHUB_RAW_URL", server.url())
         .arg("info")
         .arg("newick-utils");
 
@@ -140,7 +351,7 @@ fn command_info() -> anyhow::Result<()> {
 
     // Test JSON output
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RAW_URL", &server.url())
+    cmd.env("GITHUB_RAW_URL", server.url())
         .arg("info")
         .arg("newick-utils")
         .arg("--json");
@@ -157,7 +368,7 @@ fn command_info() -> anyhow::Result<()> {
         .create();
 
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RAW_URL", &server.url())
+    cmd.env("GITHUB_RAW_URL", server.url())
         .arg("info")
         .arg("non-existent");
 
@@ -198,7 +409,7 @@ fn command_build_source() -> anyhow::Result<()> {
 
     // Run download command
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RELEASE_URL", &server.url())
+    cmd.env("GITHUB_RELEASE_URL", server.url())
         .arg("build")
         .arg("source")
         .arg("--base")
@@ -242,6 +453,98 @@ fn command_build_source() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn command_build_checksum() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+
+    // Create mock server
+    let mut server = mockito::Server::new();
+    let test_package = include_bytes!("TRF-4.09.1.tar.gz");
+    let _m1 = server
+        .mock(
+            "GET",
+            "/Benson-Genomics-Lab/TRF/archive/refs/tags/v4.09.1.tar.gz",
+        )
+        .with_status(200)
+        .with_header("content-type", "application/gzip")
+        .with_body(test_package)
+        .expect_at_least(1)
+        .create();
+
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let cargo_dir =
+        std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let json_path = temp_dir.path().join("packages/trf.json");
+    let json = std::fs::read_to_string(
+        std::path::Path::new(&cargo_dir).join("packages/trf.json"),
+    )?;
+
+    // A wrong checksum stops the build
+    let bad = "0".repeat(64);
+    std::fs::write(
+        &json_path,
+        json.replacen(
+            ".tar.gz\",",
+            &format!(".tar.gz\",\n      \"sha256\": \"{}\",", bad),
+            1,
+        ),
+    )?;
+    Command::cargo_bin("cbp")?
+        .env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Checksum mismatch"));
+    assert!(!temp_dir.path().join("sources/trf.tar.gz").exists());
+
+    // Fill in the checksum
+    let sha256 = {
+        use sha2::Digest;
+        format!("{:x}", sha2::Sha256::digest(test_package))
+    };
+    Command::cargo_bin("cbp")?
+        .env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("checksum")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "source: {} -> {}",
+            bad, sha256
+        )));
+
+    // Only the checksum changed, key order and formatting are kept
+    let updated = std::fs::read_to_string(&json_path)?;
+    assert_eq!(
+        updated,
+        json.replacen(
+            ".tar.gz\",",
+            &format!(".tar.gz\",\n      \"sha256\": \"{}\",", sha256),
+            1
+        )
+    );
+
+    Command::cargo_bin("cbp")?
+        .env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf")
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("sources/trf.tar.gz").exists());
+
+    Ok(())
+}
+
 #[test]
 fn command_build_font() -> anyhow::Result<()> {
     let temp_dir = tempfile::TempDir::new()?;
@@ -269,9 +572,20 @@ fn command_build_font() -> anyhow::Result<()> {
         temp_dir.path().join("packages/charter.json"),
     )?;
 
+    // Route the font host to the mock server
+    let mirrors = temp_dir.path().join("mirrors.json");
+    std::fs::write(
+        &mirrors,
+        serde_json::json!({
+            "https://practicaltypography.com":
+                format!("{}/practicaltypography.com", server.url())
+        })
+        .to_string(),
+    )?;
+
     // Run font command
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RELEASE_URL", &server.url())
+    cmd.env("CBP_MIRRORS", &mirrors)
         .arg("build")
         .arg("font")
         .arg("--base")
@@ -336,7 +650,7 @@ fn command_build_prebuild_binary() -> anyhow::Result<()> {
 
     // Run prebuild command
     let mut cmd = Command::cargo_bin("cbp")?;
-    cmd.env("GITHUB_RELEASE_URL", &server.url())
+    cmd.env("GITHUB_RELEASE_URL", server.url())
         .arg("build")
         .arg("prebuild")
         .arg("--base")
@@ -368,3 +682,719 @@ fn command_build_prebuild_binary() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn command_build_prebuild_gz() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+
+    // Create mock server
+    let mut server = mockito::Server::new();
+
+    // A single gzip-compressed binary, detected by its magic bytes
+    let mut encoder =
+        flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, include_bytes!("jq-macos-arm64"))?;
+    let test_package = encoder.finish()?;
+
+    let _m1 = server
+        .mock(
+            "GET",
+            "/freebayes/freebayes/releases/download/v1.3.6/freebayes-1.3.6-linux-amd64-static.gz",
+        )
+        .with_status(200)
+        .with_header("content-type", "application/octet-stream")
+        .with_body(test_package)
+        .create();
+
+    // Create package directory and copy the package JSON
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let cargo_dir =
+        std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    std::fs::copy(
+        std::path::Path::new(&cargo_dir).join("packages/freebayes.json"),
+        temp_dir.path().join("packages/freebayes.json"),
+    )?;
+
+    // Run prebuild command
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("prebuild")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("--type")
+        .arg("linux")
+        .arg("freebayes");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-> Package created successfully"));
+
+    // Verify build results
+    let output_tar = temp_dir.path().join("binaries/freebayes.linux.tar.gz");
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("bin/freebayes"));
+    assert!(!files.contains(".gz"));
+
+    Ok(())
+}
+
+#[test]
+fn command_build_prebuild_licenses() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut server = mockito::Server::new();
+
+    // A release tarball with a binary next to its license
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    for (path, content, mode) in [
+        ("tool", &include_bytes!("jq-macos-arm64")[..], 0o755),
+        ("LICENSE", &b"MIT License\n"[..], 0o644),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content)?;
+    }
+    let test_package = builder.into_inner()?.finish()?;
+
+    let _m1 = server
+        .mock("GET", "/tool-1.0.tar.gz")
+        .with_status(200)
+        .with_body(test_package)
+        .create();
+
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let json = serde_json::json!({
+        "name": "tool",
+        "version": "1.0",
+        "description": "Demo tool",
+        "homepage": "https://example.com/tool",
+        "license": "MIT",
+        "type": "prebuild",
+        "downloads": {
+            "linux": {
+                "url": format!("{}/tool-1.0.tar.gz", server.url()),
+                "binary": "tool"
+            }
+        }
+    });
+    std::fs::write(
+        temp_dir.path().join("packages/tool.json"),
+        serde_json::to_string_pretty(&json)?,
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.arg("build")
+        .arg("prebuild")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("--type")
+        .arg("linux")
+        .arg("tool");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("-> Package created successfully"));
+
+    // Binary packages also ship the license of the release
+    let output_tar = temp_dir.path().join("binaries/tool.linux.tar.gz");
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("bin/tool"));
+    assert!(files.contains("share/licenses/tool/LICENSE"));
+
+    Ok(())
+}
+
+#[test]
+fn command_build_livecheck() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut server = mockito::Server::new();
+
+    // jq: latest release
+    let _m1 = server
+        .mock("GET", "/repos/jqlang/jq/releases/latest")
+        .with_status(200)
+        .with_body(r#"{"tag_name": "jq-1.8.0"}"#)
+        .create();
+
+    // demo: no releases, highest stable tag wins
+    let _m2 = server
+        .mock("GET", "/repos/demo/demo/releases/latest")
+        .with_status(404)
+        .create();
+    let _m3 = server
+        .mock("GET", "/repos/demo/demo/tags?per_page=100")
+        .with_status(200)
+        .with_body(
+            r#"[{"name": "v2.0.0-rc1"}, {"name": "v1.10.0"}, {"name": "v1.9.0"}]"#,
+        )
+        .create();
+
+    // pigz: versions scanned from a page
+    let _m4 = server
+        .mock("GET", "/pigz/")
+        .with_status(200)
+        .with_body("<a href=\"pigz-2.7.tar.gz\">\n<a href=\"pigz-2.8.tar.gz\">\n")
+        .create();
+
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let cargo_dir =
+        std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    std::fs::copy(
+        std::path::Path::new(&cargo_dir).join("packages/jq.json"),
+        temp_dir.path().join("packages/jq.json"),
+    )?;
+    std::fs::write(
+        temp_dir.path().join("packages/demo.json"),
+        r#"{
+  "name": "demo",
+  "version": "1.10.0",
+  "description": "Demo",
+  "homepage": "https://github.com/demo/demo",
+  "license": "MIT",
+  "type": "rust"
+}"#,
+    )?;
+    std::fs::write(
+        temp_dir.path().join("packages/pigz.json"),
+        format!(
+            r#"{{
+  "name": "pigz",
+  "version": "2.8",
+  "description": "Parallel gzip",
+  "homepage": "https://zlib.net/pigz/",
+  "license": "Zlib",
+  "type": "make",
+  "livecheck": {{
+    "url": "{}/pigz/",
+    "regex": "pigz-([\\d.]+)\\.tar\\.gz"
+  }}
+}}"#,
+            server.url()
+        ),
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_API_URL", server.url())
+        .arg("build")
+        .arg("livecheck")
+        .arg("--base")
+        .arg(temp_dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("demo: 1.10.0 (up to date)"))
+        .stdout(predicate::str::contains("jq: 1.7.1 -> 1.8.0"))
+        .stdout(predicate::str::contains("pigz: 2.8 (up to date)"))
+        .stdout(predicate::str::contains("==> 1 of 3 packages outdated"));
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    let output = cmd
+        .env("GITHUB_API_URL", server.url())
+        .arg("build")
+        .arg("livecheck")
+        .arg("--outdated")
+        .arg("--json")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .output()?;
+    let checks: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(checks.as_array().unwrap().len(), 1);
+    assert_eq!(checks[0]["name"], "jq");
+    assert_eq!(checks[0]["latest"], "1.8.0");
+    assert_eq!(checks[0]["source"], "https://github.com/jqlang/jq");
+
+    Ok(())
+}
+
+#[test]
+fn command_build_livecheck_no_group() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    std::fs::write(
+        temp_dir.path().join("packages/pigz.json"),
+        r#"{
+  "name": "pigz",
+  "version": "2.8",
+  "description": "Parallel gzip",
+  "homepage": "https://zlib.net/pigz/",
+  "license": "Zlib",
+  "type": "make",
+  "livecheck": {
+    "url": "http://127.0.0.1:9/pigz/",
+    "regex": "pigz-[\\d.]+\\.tar\\.gz"
+  }
+}"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.arg("build")
+        .arg("livecheck")
+        .arg("--base")
+        .arg(temp_dir.path());
+    cmd.assert().success().stdout(predicate::str::contains(
+        "pigz: 2.8 (error: livecheck.regex needs a capture group for the version)",
+    ));
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn command_build_bump() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let base = temp_dir.path().join("base");
+    let mut server = mockito::Server::new();
+
+    // Release archives, the one of 1.2.0 reports a wrong version
+    let release = |version: &str, output: &str| -> anyhow::Result<Vec<u8>> {
+        let script = format!("#!/bin/sh\necho demo {}\n", output);
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        archive.append_data(
+            &mut header,
+            format!("demo-{}/demo", version),
+            script.as_bytes(),
+        )?;
+        Ok(archive.into_inner()?.finish()?)
+    };
+    let body = release("1.1.0", "1.1.0")?;
+    let broken = release("1.2.0", "broken")?;
+
+    let mut mocks = vec![];
+    for (version, body) in [("1.1.0", &body), ("1.2.0", &broken)] {
+        for os in ["linux", "macos"] {
+            let path = format!(
+                "/demo/demo/releases/download/v{0}/demo-{0}-{1}.tar.gz",
+                version, os
+            );
+            mocks.push(
+                server
+                    .mock("GET", path.as_str())
+                    .with_status(200)
+                    .with_body(body)
+                    .create(),
+            );
+        }
+        let path = format!("/demo/demo/releases/download/v{0}/data-{0}.tar.gz", version);
+        mocks.push(
+            server
+                .mock("GET", path.as_str())
+                .with_status(200)
+                .with_body("data")
+                .create(),
+        );
+    }
+
+    // Templated linux URL, literal macos URL, file rules and resource
+    std::fs::create_dir_all(base.join("packages"))?;
+    std::fs::write(
+        base.join("packages/demo.json"),
+        r#"{
+  "name": "demo",
+  "version": "1.0.0",
+  "description": "Demo",
+  "homepage": "https://github.com/demo/demo",
+  "license": "MIT",
+  "type": "prebuild",
+  "downloads": {
+    "linux": {
+      "url": "https://github.com/demo/demo/releases/download/v{{version}}/demo-{{version}}-linux.tar.gz",
+      "binary": "demo-{{version}}/demo"
+    },
+    "macos": {
+      "url": "https://github.com/demo/demo/releases/download/v1.0.0/demo-1.0.0-macos.tar.gz",
+      "rename": {
+        "demo-1.0.0/demo": "libexec/demo-1.0.0"
+      },
+      "symlink": {
+        "demo": "../libexec/demo-1.0.0"
+      },
+      "clean": [
+        "demo-1.0.0"
+      ],
+      "resources": [
+        {
+          "url": "https://github.com/demo/demo/releases/download/v1.0.0/data-1.0.0.tar.gz",
+          "dest": "data"
+        }
+      ]
+    }
+  },
+  "tests": [
+    {
+      "name": "version",
+      "command": "demo",
+      "pattern": "demo 1\\.1\\.0",
+      "args": "--version"
+    }
+  ]
+}
+"#,
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("bump")
+        .arg("demo")
+        .arg("1.1.0")
+        .arg("--base")
+        .arg(&base);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("==> Bumping demo: 1.0.0 -> 1.1.0"))
+        .stdout(predicate::str::contains("PASSED"))
+        .stdout(predicate::str::contains("==> Bumped demo to 1.1.0"));
+
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        base.join("packages/demo.json"),
+    )?)?;
+    assert_eq!(json["version"], "1.1.0");
+    assert_eq!(
+        json["downloads"]["linux"]["url"],
+        "https://github.com/demo/demo/releases/download/v{{version}}/demo-{{version}}-linux.tar.gz"
+    );
+    assert_eq!(
+        json["downloads"]["macos"]["url"],
+        "https://github.com/demo/demo/releases/download/v1.1.0/demo-1.1.0-macos.tar.gz"
+    );
+    let macos = &json["downloads"]["macos"];
+    assert_eq!(
+        macos["rename"],
+        serde_json::json!({ "demo-1.1.0/demo": "libexec/demo-1.1.0" })
+    );
+    assert_eq!(
+        macos["symlink"],
+        serde_json::json!({ "demo": "../libexec/demo-1.1.0" })
+    );
+    assert_eq!(macos["clean"], serde_json::json!(["demo-1.1.0"]));
+    assert_eq!(
+        macos["resources"][0]["url"],
+        "https://github.com/demo/demo/releases/download/v1.1.0/data-1.1.0.tar.gz"
+    );
+    assert_eq!(
+        json["downloads"]["linux"]["sha256"],
+        cbp::sha256_file(&{
+            let path = temp_dir.path().join("body.tar.gz");
+            std::fs::write(&path, &body)?;
+            path
+        })?
+    );
+    let archive = base.join("binaries/demo.linux.tar.gz");
+    let bumped = std::fs::read(&archive)?;
+
+    // Failed tests restore the package JSON and the archives
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("bump")
+        .arg("demo")
+        .arg("1.2.0")
+        .arg("--base")
+        .arg(&base);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to bump demo"));
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        base.join("packages/demo.json"),
+    )?)?;
+    assert_eq!(json["version"], "1.1.0");
+    assert_eq!(std::fs::read(&archive)?, bumped);
+
+    // A failed download restores the package JSON
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("bump")
+        .arg("demo")
+        .arg("9.9.9")
+        .arg("--base")
+        .arg(&base)
+        .arg("--no-test");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to bump demo"));
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        base.join("packages/demo.json"),
+    )?)?;
+    assert_eq!(json["version"], "1.1.0");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn command_build_source_patches() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut server = mockito::Server::new();
+
+    let _m1 = server
+        .mock(
+            "GET",
+            "/Benson-Genomics-Lab/TRF/archive/refs/tags/v4.09.1.tar.gz",
+        )
+        .with_status(200)
+        .with_body(include_bytes!("TRF-4.09.1.tar.gz"))
+        .create();
+    let _m2 = server
+        .mock("GET", "/demo/data/raw/main/test.fa")
+        .with_status(200)
+        .with_body(">seq\nACGT\n")
+        .create();
+
+    // trf.json with a patch against the renamed source directory, and a
+    // resource placed beside it
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let cargo_dir =
+        std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        std::path::Path::new(&cargo_dir).join("packages/trf.json"),
+    )?)?;
+    json["downloads"]["source"]["patches"] = serde_json::json!(["install.patch"]);
+    json["downloads"]["source"]["resources"] = serde_json::json!([{
+        "url": "https://github.com/demo/data/raw/main/test.fa",
+        "dest": "data"
+    }]);
+    let json_path = temp_dir.path().join("packages/trf.json");
+    std::fs::write(&json_path, serde_json::to_string_pretty(&json)?)?;
+
+    let patch_dir = temp_dir.path().join("patches/trf");
+    std::fs::create_dir_all(&patch_dir)?;
+    std::fs::write(
+        patch_dir.join("install.patch"),
+        "--- a/INSTALL\n+++ b/INSTALL\n@@ -1,3 +1,3 @@\n-To compile TRF, you will need a C compiler (e.g., gcc, clang) with the standard library installed. We have tested compiling and installing TRF under UNIX-based systems (Linux, macOS) and for Windows under Cygwin/MinGW.\n+To compile TRF, use zig cc.\n \n Brief instructions:\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf");
+    cmd.assert().success();
+
+    // The patched file ends up in the source archive
+    let output_tar = temp_dir.path().join("sources/trf.tar.gz");
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+        std::fs::File::open(&output_tar)?,
+    ));
+    let mut install = String::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.ends_with("trf/INSTALL") {
+            std::io::Read::read_to_string(&mut entry, &mut install)?;
+        }
+    }
+    assert!(install.starts_with("To compile TRF, use zig cc.\n"));
+    let files = cbp::list_archive_files(&output_tar)?;
+    assert!(files.contains("data/test.fa"));
+
+    // A patch that does not apply stops the build
+    std::fs::write(
+        patch_dir.join("install.patch"),
+        "--- a/INSTALL\n+++ b/INSTALL\n@@ -1 +1 @@\n-No such line\n+Replacement\n",
+    )?;
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Patch install.patch does not apply to trf",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn command_build_source_resources() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut server = mockito::Server::new();
+
+    let _m1 = server
+        .mock(
+            "GET",
+            "/Benson-Genomics-Lab/TRF/archive/refs/tags/v4.09.1.tar.gz",
+        )
+        .with_status(200)
+        .with_body(include_bytes!("TRF-4.09.1.tar.gz"))
+        .create();
+
+    // A vendored library archive and a plain data file
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    for name in ["lib-1.0/lib.c", "lib-1.0/docs/manual.txt"] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, name, &b"data"[..])?;
+    }
+    let _m2 = server
+        .mock("GET", "/demo/lib/archive/v1.0.tar.gz")
+        .with_status(200)
+        .with_body(archive.into_inner()?.finish()?)
+        .create();
+    let _m3 = server
+        .mock("GET", "/demo/data/raw/main/test.fa")
+        .with_status(200)
+        .with_body(">seq\nACGT\n")
+        .create();
+    let _m4 = server
+        .mock("GET", "/demo/data/raw/v4.09.1/notes-4.09.1.txt")
+        .with_status(200)
+        .with_body("notes\n")
+        .create();
+
+    std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+    let cargo_dir =
+        std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        std::path::Path::new(&cargo_dir).join("packages/trf.json"),
+    )?)?;
+    json["downloads"]["source"]["resources"] = serde_json::json!([
+        {
+            "url": "https://github.com/demo/lib/archive/v1.0.tar.gz",
+            "dest": "trf/vendor",
+            "rename": { "lib-*": "lib" },
+            "clean": ["lib/docs"]
+        },
+        {
+            "url": "https://github.com/demo/data/raw/main/test.fa",
+            "dest": "trf/test"
+        },
+        {
+            "url": "https://github.com/demo/data/raw/v{{version}}/notes-{{version}}.txt",
+            "dest": "trf/notes"
+        }
+    ]);
+    std::fs::write(
+        temp_dir.path().join("packages/trf.json"),
+        serde_json::to_string_pretty(&json)?,
+    )?;
+
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("GITHUB_RELEASE_URL", server.url())
+        .arg("build")
+        .arg("source")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("trf");
+    cmd.assert().success().stdout(predicate::str::contains(
+        "-> Downloading resource trf/vendor",
+    ));
+
+    let files = cbp::list_archive_files(&temp_dir.path().join("sources/trf.tar.gz"))?;
+    assert!(files.contains("trf/INSTALL"));
+    assert!(files.contains("trf/vendor/lib/lib.c"));
+    assert!(!files.contains("trf/vendor/lib/docs")); // Should be cleaned
+    assert!(files.contains("trf/test/test.fa"));
+    assert!(files.contains("trf/notes/notes-4.09.1.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn command_build_prebuild_mirrors() -> anyhow::Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut server = mockito::Server::new();
+
+    let _m1 = server
+        .mock("GET", "/down/jq-macos-arm64")
+        .with_status(503)
+        .create();
+    let _m2 = server
+        .mock("GET", "/mirror/jq-macos-arm64")
+        .with_status(200)
+        .with_body(include_bytes!("jq-macos-arm64"))
+        .create();
+
+    // Both hosts are rewritten to the mock server
+    let mirrors = temp_dir.path().join("mirrors.json");
+    std::fs::write(
+        &mirrors,
+        serde_json::json!({
+            "https://down.example.com": format!("{}/down", server.url()),
+            "https://mirror.example.com": format!("{}/mirror", server.url()),
+        })
+        .to_string(),
+    )?;
+
+    let write_package = |urls: serde_json::Value| -> anyhow::Result<()> {
+        std::fs::create_dir_all(temp_dir.path().join("packages"))?;
+        let json = serde_json::json!({
+            "name": "jq",
+            "version": "1.7.1",
+            "description": "Command-line JSON processor",
+            "homepage": "https://jqlang.github.io/jq/",
+            "license": "MIT",
+            "type": "prebuild",
+            "downloads": {
+                "macos": { "url": urls, "binary": "jq" }
+            }
+        });
+        std::fs::write(
+            temp_dir.path().join("packages/jq.json"),
+            serde_json::to_string_pretty(&json)?,
+        )?;
+        Ok(())
+    };
+
+    // The first mirror fails, the second one is used
+    write_package(serde_json::json!([
+        "https://down.example.com/jq-macos-arm64",
+        "https://mirror.example.com/jq-macos-arm64"
+    ]))?;
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("CBP_MIRRORS", &mirrors)
+        .arg("build")
+        .arg("prebuild")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("jq");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("/down/jq-macos-arm64 failed"))
+        .stdout(predicate::str::contains("-> Package created successfully"));
+    let archive = temp_dir.path().join("binaries/jq.macos.tar.gz");
+    let metadata = cbp::read_package_metadata(&archive)?.unwrap();
+    assert_eq!(metadata.version, "1.7.1");
+    assert_eq!(metadata.license.as_deref(), Some("MIT"));
+    assert_eq!(
+        metadata.source.as_deref(),
+        Some("https://down.example.com/jq-macos-arm64")
+    );
+
+    // Every failure is reported
+    write_package(serde_json::json!([
+        "https://down.example.com/jq-macos-arm64",
+        "https://down.example.com/jq-macos-arm64?retry=1"
+    ]))?;
+    let mut cmd = Command::cargo_bin("cbp")?;
+    cmd.env("CBP_MIRRORS", &mirrors)
+        .arg("build")
+        .arg("prebuild")
+        .arg("--base")
+        .arg(temp_dir.path())
+        .arg("jq");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("All 2 mirrors failed"))
+        .stderr(predicate::str::contains("retry=1"));
+
+    Ok(())
+}
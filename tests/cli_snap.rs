@@ -1222,7 +1222,7 @@ fn command_snap_archive_not_found_delta() -> anyhow::Result<()> {
     Command::cargo_bin("cbp")?
         .arg("snap")
         .arg("delta")
-        .arg(&nonexistent)
+        .arg(nonexistent)
         .assert()
         .failure()
         .stderr(predicate::str::contains("not found"));
@@ -1237,7 +1237,7 @@ fn command_snap_archive_not_found_load() -> anyhow::Result<()> {
     Command::cargo_bin("cbp")?
         .arg("snap")
         .arg("load")
-        .arg(&nonexistent)
+        .arg(nonexistent)
         .assert()
         .failure()
         .stderr(predicate::str::contains("not found"));
@@ -1386,3 +1386,66 @@ fn command_snap_save_multiple_files_verify_content() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn command_snap_compression() -> anyhow::Result<()> {
+    for (compression, ext) in [("zstd", "tar.zst"), ("xz", "tar.xz")] {
+        let temp_dir = tempfile::TempDir::new()?;
+        let source_dir = temp_dir.path().join("dotfiles");
+        std::fs::create_dir_all(&source_dir)?;
+        std::fs::write(source_dir.join(".bashrc"), "alias ll='ls -la'\n")?;
+
+        // Default name follows the compression
+        Command::cargo_bin("cbp")?
+            .arg("snap")
+            .arg("save")
+            .arg(&source_dir)
+            .arg("--compression")
+            .arg(compression)
+            .current_dir(&temp_dir)
+            .assert()
+            .success();
+        let archive_path = temp_dir.path().join(format!("dotfiles.snap.{}", ext));
+        assert!(archive_path.exists());
+
+        // Source paths survive without a gzip comment
+        Command::cargo_bin("cbp")?
+            .arg("snap")
+            .arg("list")
+            .arg(&archive_path)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("dotfiles"))
+            .stdout(predicate::str::contains("1 files"));
+
+        // Delta keeps the compression
+        std::fs::write(source_dir.join(".bashrc"), "alias g='git'\n")?;
+        Command::cargo_bin("cbp")?
+            .arg("snap")
+            .arg("delta")
+            .arg(&archive_path)
+            .arg("-p")
+            .current_dir(&temp_dir)
+            .assert()
+            .success();
+        let delta_path = temp_dir.path().join(format!("dotfiles.delta.{}", ext));
+        assert!(delta_path.exists());
+
+        let restore_dir = temp_dir.path().join("restored");
+        Command::cargo_bin("cbp")?
+            .arg("snap")
+            .arg("load")
+            .arg(&delta_path)
+            .arg("-t")
+            .arg(&restore_dir)
+            .assert()
+            .success();
+        assert_eq!(
+            std::fs::read_to_string(restore_dir.join("dotfiles/.bashrc"))?,
+            "alias g='git'\n"
+        );
+        assert!(!restore_dir.join("pax_global_header").exists());
+    }
+
+    Ok(())
+}
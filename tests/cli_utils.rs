@@ -148,6 +148,228 @@ fn command_tar_symlink() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn command_tar_reproducible() -> anyhow::Result<()> {
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let collect_dir = temp_dir.path().join("collect");
+    fs::create_dir_all(collect_dir.join("bin"))?;
+    fs::write(collect_dir.join("bin/hello"), "#!/bin/sh\necho hello\n")?;
+    fs::write(collect_dir.join("README"), "readme")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(
+            collect_dir.join("bin/hello"),
+            fs::Permissions::from_mode(0o700),
+        )?;
+    }
+
+    let run_tar = |name: &str| -> anyhow::Result<Vec<u8>> {
+        Command::cargo_bin("cbp")?
+            .arg("tar")
+            .arg(&collect_dir)
+            .arg("-o")
+            .arg(name)
+            .arg("--verify-reproducible")
+            .current_dir(temp_dir.path())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("-> Reproducible, sha256: "));
+        Ok(fs::read(temp_dir.path().join(name))?)
+    };
+
+    let first = run_tar("first.tar.gz")?;
+
+    // Touching files doesn't change the archive
+    let mtime =
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1e9 as u64);
+    fs::File::options()
+        .write(true)
+        .open(collect_dir.join("README"))?
+        .set_modified(mtime)?;
+    let second = run_tar("second.tar.gz")?;
+    assert_eq!(first, second);
+
+    // Sorted entries with normalized headers
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&first[..]));
+    let mut paths = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        assert_eq!(header.mtime()?, 1704067200);
+        assert_eq!(header.uid()?, 0);
+        assert_eq!(header.gid()?, 0);
+        assert_eq!(header.username()?, Some(""));
+        paths.push((entry.path()?.to_string_lossy().into_owned(), header.mode()?));
+    }
+    let exe_mode = if cfg!(unix) { 0o755 } else { 0o644 };
+    assert_eq!(
+        paths,
+        vec![
+            ("README".to_string(), 0o644),
+            ("bin/hello".to_string(), exe_mode)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn command_tar_compression() -> anyhow::Result<()> {
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let collect_dir = temp_dir.path().join("hello");
+    fs::create_dir_all(collect_dir.join("bin"))?;
+    fs::write(collect_dir.join("bin/hello"), "#!/bin/sh\necho hello\n")?;
+
+    for (compression, format) in [
+        ("gzip", cbp::CompressionFormat::Gzip),
+        ("zstd", cbp::CompressionFormat::Zstd),
+        ("xz", cbp::CompressionFormat::Xz),
+    ] {
+        // Default name follows the compression
+        Command::cargo_bin("cbp")?
+            .arg("tar")
+            .arg(&collect_dir)
+            .arg("--compression")
+            .arg(compression)
+            .arg("--metadata")
+            .arg(r#"{"name": "hello", "version": "1.0.0"}"#)
+            .current_dir(temp_dir.path())
+            .assert()
+            .success();
+        let tar_name = temp_dir
+            .path()
+            .join(format!("hello.{}", format.extension()));
+        assert_eq!(cbp::CompressionFormat::detect(&tar_name)?, Some(format));
+        assert_eq!(cbp::list_archive_files(&tar_name)?, "bin/hello\n");
+        let metadata = cbp::read_package_metadata(&tar_name)?.unwrap();
+        assert_eq!(metadata.version, "1.0.0");
+    }
+
+    // Installing relies on content, not on the file name
+    let os_type = cbp::get_os_type()?;
+    let cbp_home = temp_dir.path().join("home");
+    fs::create_dir_all(cbp_home.join("cache"))?;
+    fs::copy(
+        temp_dir.path().join("hello.tar.zst"),
+        cbp_home.join(format!("cache/hello.{}.tar.gz", os_type)),
+    )?;
+    fs::create_dir_all(temp_dir.path().join("binaries"))?;
+    fs::copy(
+        temp_dir.path().join("hello.tar.xz"),
+        temp_dir
+            .path()
+            .join(format!("binaries/hello.{}.tar.xz", os_type)),
+    )?;
+
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--list")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("hello")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("binaries/"))
+        .stdout(predicate::str::contains("bin/hello"));
+
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("hello")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cache/"));
+    assert!(cbp_home.join("bin/hello").exists());
+
+    Ok(())
+}
+
+#[test]
+fn command_tar_metadata() -> anyhow::Result<()> {
+    use std::fs;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new()?;
+    let collect_dir = temp_dir.path().join("collect");
+    fs::create_dir_all(collect_dir.join("bin"))?;
+    fs::write(collect_dir.join("bin/hello"), "#!/bin/sh\necho hello\n")?;
+
+    let os_type = cbp::get_os_type()?;
+    let tar_name = format!("binaries/hello.{}.tar.gz", os_type);
+    fs::create_dir_all(temp_dir.path().join("binaries"))?;
+
+    Command::cargo_bin("cbp")?
+        .arg("tar")
+        .arg(&collect_dir)
+        .arg("-o")
+        .arg(&tar_name)
+        .arg("--metadata")
+        .arg("{\"name\": \"hello\"}")
+        .current_dir(temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --metadata"));
+
+    Command::cargo_bin("cbp")?
+        .arg("tar")
+        .arg(&collect_dir)
+        .arg("-o")
+        .arg(&tar_name)
+        .arg("--metadata")
+        .arg(
+            r#"{"name": "hello", "version": "1.0.0", "license": "MIT",
+                "commit": "e8da7ed", "built": "2024-01-01T00:00:00Z"}"#,
+        )
+        .arg("--verify-reproducible")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success();
+
+    // The metadata header is not a file of the package
+    let archive = temp_dir.path().join(&tar_name);
+    assert_eq!(cbp::list_archive_files(&archive)?, "bin/hello\n");
+
+    // Gzip archives carry it in the gzip header, so plain readers see no extra entry
+    let decoder = flate2::read::GzDecoder::new(fs::File::open(&archive)?);
+    let mut plain = tar::Archive::new(decoder);
+    let paths: Vec<_> = plain
+        .entries()?
+        .map(|e| Ok(e?.path()?.to_string_lossy().into_owned()))
+        .collect::<anyhow::Result<_>>()?;
+    assert_eq!(paths, vec!["bin/hello"]);
+
+    // Installing saves the metadata into the record
+    let cbp_home = temp_dir.path().join("home");
+    Command::cargo_bin("cbp")?
+        .arg("local")
+        .arg("--dir")
+        .arg(&cbp_home)
+        .arg("hello")
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Version 1.0.0"));
+
+    let record: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(cbp_home.join("records/hello.json"))?)?;
+    assert_eq!(record["explicit"], true);
+    assert_eq!(record["metadata"]["version"], "1.0.0");
+    assert_eq!(record["metadata"]["commit"], "e8da7ed");
+    assert_eq!(record["metadata"]["built"], "2024-01-01T00:00:00Z");
+    assert!(!cbp_home.join("pax_global_header").exists());
+
+    Ok(())
+}
+
 #[test]
 fn command_prefix() -> anyhow::Result<()> {
     use tempfile::TempDir;